@@ -1,15 +1,30 @@
 
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod cigar{
+    #[cfg(feature = "std")]
     use strand_specifier_lib::{Strand};
-    use std::str::FromStr;
-    use std::fmt;
+    use core::str::FromStr;
+    use core::fmt;
+    use core::fmt::Write;
+    use core::ops::{Add, AddAssign, Range};
+    use core::iter::Sum;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use alloc::string::String;
+    use alloc::format;
 
-    #[derive(Debug, PartialEq)]
-    /// Basic Cigar Operation, does not accept "X" or "=".
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    /// Basic Cigar Operation.
+    /// SeqMatch (=) and SeqMismatch (X) are aligned blocks like Match, with the base comparison made explicit.
     pub enum CigarOperation{
         Nskipped(i64),
         Match(i64),
+        SeqMatch(i64),
+        SeqMismatch(i64),
         Insertion(i64),
         Deletion(i64),
         Soft(i64),
@@ -19,37 +34,238 @@ pub mod cigar{
         Invalid
     }
 
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    /// Which sequence(s) an operation consumes, see CigarOperation::class().
+    /// Walks over the cigar should rely on it rather than matching operations one by one,
+    /// so a new operation only has to be classified here.
+    pub struct OpClass{
+        pub consumes_ref: bool,
+        pub consumes_query: bool,
+    }
+
     impl CigarOperation{
+        /// return which sequence(s) the operation consume.
+        pub fn class(&self) -> OpClass{
+            let (consumes_ref, consumes_query) = match self{
+                CigarOperation::Nskipped(_) => (true, false),
+                CigarOperation::Match(_) | CigarOperation::SeqMatch(_) | CigarOperation::SeqMismatch(_) => (true, true),
+                CigarOperation::Insertion(_)  => (false, true),
+                CigarOperation::Deletion(_)  => (true, false),
+                CigarOperation::Soft(_)  => (false, true),
+                CigarOperation::Hard(_)  => (false, false),
+                CigarOperation::Padded(_)  => (false, false),
+                _ => (false, false)
+            };
+            OpClass{ consumes_ref, consumes_query }
+        }
+
         /// utility function return if the operation consume the reference
         pub fn consume_ref(&self) -> bool{
-            match self{
-                CigarOperation::Nskipped(_) => true,
-                CigarOperation::Match(_)  => true,
-                CigarOperation::Insertion(_)  => false,
-                CigarOperation::Deletion(_)  => true,
-                CigarOperation::Soft(_)  => false,
-                CigarOperation::Hard(_)  => false,
-                CigarOperation::Padded(_)  => false,
-                _ => false
-            }
+            self.class().consumes_ref
         }
         
+        /// utility function return if the operation consume the Query
         pub fn consume_que(&self) -> bool{
-            /// utility function return if the operation consume the Query
+            self.class().consumes_query
+        }
+
+        /// utility function return if the operation is an aligned block (consume both the reference and the query)
+        pub fn is_aligned(&self) -> bool{
+            let class = self.class();
+            class.consumes_ref && class.consumes_query
+        }
+
+        /// return the symbol of the operation as written in a cigar string.
+        pub fn symbol(&self) -> char{
+            match self{
+                CigarOperation::Nskipped(_) => 'N',
+                CigarOperation::Match(_) => 'M',
+                CigarOperation::SeqMatch(_) => '=',
+                CigarOperation::SeqMismatch(_) => 'X',
+                CigarOperation::Insertion(_) => 'I',
+                CigarOperation::Deletion(_) => 'D',
+                CigarOperation::Soft(_) => 'S',
+                CigarOperation::Hard(_) => 'H',
+                CigarOperation::Padded(_) => 'P',
+                CigarOperation::Unaligned => '*',
+                CigarOperation::Invalid => '?',
+            }
+        }
+
+        /// return the length of the operation, 0 for Unaligned and Invalid.
+        pub fn length(&self) -> i64{
+            match self{
+                CigarOperation::Nskipped(n) | CigarOperation::Match(n) | CigarOperation::SeqMatch(n) |
+                CigarOperation::SeqMismatch(n) | CigarOperation::Insertion(n) | CigarOperation::Deletion(n) | CigarOperation::Soft(n) | CigarOperation::Hard(n) |
+                CigarOperation::Padded(n) => *n,
+                _ => 0
+            }
+        }
+
+        /// return the BAM op code of the operation (M=0, I=1, D=2, N=3, S=4, H=5, P=6, ==7, X=8), None for Unaligned and Invalid.
+        pub fn bam_code(&self) -> Option<u32>{
+            match self{
+                CigarOperation::Match(_) => Some(0),
+                CigarOperation::Insertion(_) => Some(1),
+                CigarOperation::Deletion(_) => Some(2),
+                CigarOperation::Nskipped(_) => Some(3),
+                CigarOperation::Soft(_) => Some(4),
+                CigarOperation::Hard(_) => Some(5),
+                CigarOperation::Padded(_) => Some(6),
+                CigarOperation::SeqMatch(_) => Some(7),
+                CigarOperation::SeqMismatch(_) => Some(8),
+                _ => None
+            }
+        }
+
+        /// return true for a soft or hard clip, query only (S) or neither (H) operations that are not part of the alignment.
+        fn is_clip(&self) -> bool{
+            matches!(self, CigarOperation::Soft(_) | CigarOperation::Hard(_))
+        }
+
+        /// build the operation of length n from its symbol, Invalid if the symbol is unknown.
+        fn from_symbol(symbol: char, n: i64) -> CigarOperation{
+            match symbol{
+                'M' => CigarOperation::Match(n),
+                'I' => CigarOperation::Insertion(n),
+                'D' => CigarOperation::Deletion(n),
+                'N' => CigarOperation::Nskipped(n),
+                'S' => CigarOperation::Soft(n),
+                'H' => CigarOperation::Hard(n),
+                'P' => CigarOperation::Padded(n),
+                '=' => CigarOperation::SeqMatch(n),
+                'X' => CigarOperation::SeqMismatch(n),
+                _ => CigarOperation::Invalid,
+            }
+        }
+
+        /// return the same operation with a new length.
+        fn with_length(&self, n: i64) -> CigarOperation{
+            match self{
+                CigarOperation::Nskipped(_) => CigarOperation::Nskipped(n),
+                CigarOperation::Match(_) => CigarOperation::Match(n),
+                CigarOperation::Insertion(_) => CigarOperation::Insertion(n),
+                CigarOperation::Deletion(_) => CigarOperation::Deletion(n),
+                CigarOperation::Soft(_) => CigarOperation::Soft(n),
+                CigarOperation::Hard(_) => CigarOperation::Hard(n),
+                CigarOperation::Padded(_) => CigarOperation::Padded(n),
+                CigarOperation::SeqMatch(_) => CigarOperation::SeqMatch(n),
+                CigarOperation::SeqMismatch(_) => CigarOperation::SeqMismatch(n),
+                op => *op
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Clone)]
+    /// One row of Cigar::breakdown().
+    /// ranges are half-open [start, end), None when the operation does not consume that sequence.
+    pub struct OpBreakdown{
+        pub symbol: char,
+        pub length: i64,
+        pub ref_range: Option<Range<i64>>,
+        pub query_range: Option<Range<i64>>,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    /// One difference between two operation lists, as returned by Cigar::diff().
+    /// index is the position of the operation in the cigars, expected comes from self and got from the other cigar.
+    pub enum CigarDiff{
+        /// both cigars have a different operation at index
+        Mismatch{ index: usize, expected: CigarOperation, got: CigarOperation },
+        /// the other cigar is shorter and lacks the operation at index
+        Missing{ index: usize, expected: CigarOperation },
+        /// the other cigar is longer and has an extra operation at index
+        Extra{ index: usize, got: CigarOperation },
+    }
+
+    impl fmt::Display for CigarDiff {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             match self{
-                CigarOperation::Nskipped(_)  => false,
-                CigarOperation::Match(_)  => true,
-                CigarOperation::Insertion(_)  => true,
-                CigarOperation::Deletion(_)  => false,
-                CigarOperation::Soft(_)  => true,
-                CigarOperation::Hard(_)  => false,
-                CigarOperation::Padded(_)  => false,
-                _ => false
+                CigarDiff::Mismatch{ index, expected, got } => write!(f, "position {}: expected {}({}), got {}({})",
+                    index, expected.symbol(), expected.length(), got.symbol(), got.length()),
+                CigarDiff::Missing{ index, expected } => write!(f, "position {}: expected {}({}), got nothing",
+                    index, expected.symbol(), expected.length()),
+                CigarDiff::Extra{ index, got } => write!(f, "position {}: expected nothing, got {}({})",
+                    index, got.symbol(), got.length()),
             }
         }
     }
 
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, PartialEq, Clone)]
+    /// Part of an alignment falling in a reference window, as returned by Cigar::restrict_to_window().
+    /// cigar describes exactly query[query_start..query_end] (no clips) aligned from new_ref_start.
+    pub struct WindowSlice{
+        pub cigar: Cigar,
+        pub new_ref_start: i64,
+        pub query_start: i64,
+        pub query_end: i64,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    /// End of a cigar, Five is the first operation and Three the last one (in the cigar order).
+    pub enum End{
+        Five,
+        Three,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    /// Result of Cigar::ref_to_query_detailed(), the query position aligned with a reference position or the reason there is none.
+    pub enum RefMapResult{
+        Aligned(i64),
+        Deleted,
+        Skipped,
+        OutOfRange,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    /// A change made by Cigar::repair().
+    pub enum RepairAction{
+        /// this many zero length operations were dropped
+        DroppedZeroLength(usize),
+        /// a terminal insertion was turned into a soft clip
+        InsertionToSoftClip{ end: End, length: i64 },
+        /// a terminal deletion was removed, a deletion at End::Five shifts the alignment start by length
+        StrippedDeletion{ end: End, length: i64 },
+        /// the hard clips of this end were moved outside the soft clips
+        MovedHardClip(End),
+        /// adjacent operations of the same type were merged
+        MergedAdjacent,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Default)]
+    /// Reference and query offsets before each operation of a cigar, see Cigar::offset_tables().
+    pub struct OffsetTables{
+        pub reference: Vec<i64>,
+        pub query: Vec<i64>,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    /// A pileup event yielded by Cigar::pileup_events(), positions are on the reference (ref_) or on the query (query_).
+    /// Del is yielded once per deleted reference position, Skip once per skipped (N) operation as [start, end).
+    pub enum PileupEvent{
+        Match{ ref_pos: i64, query_pos: i64 },
+        Del{ ref_pos: i64 },
+        Ins{ ref_anchor: i64, query_pos: i64, len: i64 },
+        Skip{ start: i64, end: i64 },
+    }
+
+    #[derive(Debug, PartialEq, Eq, Clone, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    /// Summary of an alignment as returned by Cigar::summary().
+    /// Serializable with the serde feature, see Cigar::summary_json().
+    pub struct CigarSummary{
+        pub query_length: i64,
+        pub reference_span: i64,
+        pub junction_count: usize,
+        pub soft_clipped: i64,
+        pub hard_clipped: i64,
+        pub insertion_count: usize,
+        pub insertion_bases: i64,
+        pub deletion_count: usize,
+        pub deletion_bases: i64,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Default)]
     /// Representation of Cigar Operation 
     /// This is the main structure users interact with.
     /// Right now it does only allow to interact with from(&str) and get_skipped_pos_on_ref().
@@ -72,7 +288,77 @@ pub mod cigar{
 
     #[derive(Debug, PartialEq, Eq)]
     pub struct ParseCigarError;
-    /// Create a new Cigar struct from a &str. the &str must be a valid cigar string without "X" or "=" operation
+
+    #[derive(Debug, PartialEq, Eq)]
+    /// Errors returned by the fallible helpers of this module.
+    pub enum CigarError{
+        /// the cigar string is not valid
+        Parse,
+        /// the SAM line does not have a cigar field (less than 6 columns)
+        MissingField,
+        /// the cigar query length is larger than the requested length
+        QueryTooLong,
+        /// a BAM encoded operation has an op code larger than 8
+        InvalidOpCode,
+        /// the alignment starts before the reference
+        NegativeStart(i64),
+        /// the alignment ends after the end of the reference
+        BeyondReference{ end: i64, ref_len: i64 },
+        /// the query length described by the cigar differs from the read length
+        QueryLengthMismatch{ cigar: i64, query: i64 },
+        /// an operation length is negative
+        NegativeLength(i64),
+        /// the operation index is larger than the number of operations
+        IndexOutOfRange(usize),
+    }
+
+    impl From<ParseCigarError> for CigarError {
+        fn from(_: ParseCigarError) -> Self {
+            CigarError::Parse
+        }
+    }
+
+    impl fmt::Display for CigarError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self{
+                CigarError::Parse => write!(f, "invalid cigar string"),
+                CigarError::MissingField => write!(f, "missing cigar field"),
+                CigarError::QueryTooLong => write!(f, "cigar query length exceeds the target length"),
+                CigarError::InvalidOpCode => write!(f, "invalid BAM cigar op code"),
+                CigarError::NegativeStart(start) => write!(f, "alignment start {} is negative", start),
+                CigarError::BeyondReference{ end, ref_len } => write!(f, "alignment end {} is beyond the reference length {}", end, ref_len),
+                CigarError::QueryLengthMismatch{ cigar, query } => write!(f, "cigar query length {} differs from the read length {}", cigar, query),
+                CigarError::NegativeLength(length) => write!(f, "operation length {} is negative", length),
+                CigarError::IndexOutOfRange(index) => write!(f, "operation index {} is out of range", index),
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl std::error::Error for CigarError {}
+
+    /// extract and parse the cigar field (6th column) of a SAM line.
+    /// "*" gives an empty (unaligned) Cigar.
+    pub fn parse_sam_cigar_field(line: &str) -> Result<Cigar, CigarError>{
+        match line.split('\t').nth(5){
+            Some(field) => Ok(Cigar::from_str(field)?),
+            None => Err(CigarError::MissingField),
+        }
+    }
+
+    /// given (cigar, aln_start) pairs, return all the junctions (donor, acceptor) as given by get_skipped_pos_on_ref(),
+    /// deduplicated and sorted by donor then acceptor.
+    pub fn collect_unique_junctions<I: IntoIterator<Item = (Cigar, i64)>>(reads: I) -> Vec<(i64, i64)>{
+        let mut junctions: Vec<(i64, i64)> = reads.into_iter()
+            .filter_map(|(cigar, aln_start)| cigar.get_skipped_pos_on_ref(&aln_start))
+            .flat_map(|positions| positions.chunks(2).map(|j| (j[0], j[1])).collect::<Vec<_>>())
+            .collect();
+        junctions.sort_unstable();
+        junctions.dedup();
+        junctions
+    }
+
+    /// Create a new Cigar struct from a &str. the &str must be a valid cigar string
     /// Will return an error if the cigar string is not valid.
     impl FromStr for Cigar {
 
@@ -93,6 +379,8 @@ pub mod cigar{
                         'S' => CigarOperation::Soft(length),
                         'H' => CigarOperation::Hard(length),
                         'P' => CigarOperation::Padded(length),
+                        '=' => CigarOperation::SeqMatch(length),
+                        'X' => CigarOperation::SeqMismatch(length),
                         '*' => CigarOperation::Unaligned,
                         _ => CigarOperation::Invalid,
                     };
@@ -115,7 +403,7 @@ pub mod cigar{
     }
 
 
-    /// Create a new Cigar struct from a &str. the &str must be a valid cigar string without "X" or "=" operation
+    /// Create a new Cigar struct from a &str. the &str must be a valid cigar string
     /// Will Panic if the cigar string is not valid.
     impl From<&str> for Cigar {
 
@@ -135,6 +423,8 @@ pub mod cigar{
                         'S' => CigarOperation::Soft(length),
                         'H' => CigarOperation::Hard(length),
                         'P' => CigarOperation::Padded(length),
+                        '=' => CigarOperation::SeqMatch(length),
+                        'X' => CigarOperation::SeqMismatch(length),
                         '*' => CigarOperation::Unaligned,
                         _ => panic!("Invalid CIGAR operation"),
                     };
@@ -148,15 +438,22 @@ pub mod cigar{
         }
     }
 
+    /// Create a new Cigar struct from a vector of operations, no check is done on the operations.
+    /// together with into_operations() this allows to decompose, transform and rebuild a Cigar without cloning.
+    impl From<Vec<CigarOperation>> for Cigar {
+
+        fn from(operations: Vec<CigarOperation>) -> Self {
+            Cigar{
+                cigar: operations
+            }
+        }
+    }
+
 
     impl Cigar{
         
         fn has_skipped(&self) -> bool{
-            self.cigar.iter()
-            .any(|e| match e{
-                CigarOperation::Nskipped(_) => true,
-                _ => false
-            })
+            self.contains_op('N')
         }
         /// given a Cigar string and the start of the alignment of a read
         /// return all the skipped position junction.
@@ -172,14 +469,14 @@ pub mod cigar{
                 let mut results = Vec::new();
 
                 for cigar_op in self.cigar.iter(){
-                    /// By definition it is impossible to have to consecutive same (N) operation.
+                    // By definition it is impossible to have to consecutive same (N) operation.
                     match cigar_op{
                         CigarOperation::Nskipped(n) => {
                             results.push(ref_pos);
                             ref_pos += n;
                             results.push(ref_pos);
                             },
-                        CigarOperation::Match(n) | CigarOperation::Deletion(n) => { ref_pos += n; }, 
+                        op if op.consume_ref() => { ref_pos += op.length(); }, 
                         _  => ()
                     }
                 }
@@ -195,6 +492,30 @@ pub mod cigar{
             }
         }
 
+        /// given the start of the alignment on the reference and on the query
+        /// return, for each operation, its symbol, length and the ranges it covers on both sequences.
+        /// usefull for debugging or to print an alignment operation by operation.
+        pub fn breakdown(&self, aln_start: i64, query_start: i64) -> Vec<OpBreakdown>{
+            let mut ref_pos = aln_start;
+            let mut que_pos = query_start;
+            let mut results = Vec::with_capacity(self.cigar.len());
+
+            for cigar_op in self.cigar.iter(){
+                let n = cigar_op.length();
+                let ref_range = if cigar_op.consume_ref(){
+                    ref_pos += n;
+                    Some(ref_pos - n..ref_pos)
+                } else { None };
+                let query_range = if cigar_op.consume_que(){
+                    que_pos += n;
+                    Some(que_pos - n..que_pos)
+                } else { None };
+                results.push(OpBreakdown{ symbol: cigar_op.symbol(), length: n, ref_range, query_range });
+            }
+            results
+        }
+
+        #[cfg(feature = "std")]
         pub fn get_soft_clipped_n(&self, strand: &Strand) -> Option<i64>{
             let mut soft_n = None;
             if *strand == Strand::Minus{
@@ -214,6 +535,7 @@ pub mod cigar{
             soft_n
         }
 
+        #[cfg(feature = "std")]
         pub fn soft_clipped_end(&self, strand: &Strand, delta: i64) -> bool{
             if *strand == Strand::Minus{
                 match self.cigar[0]{
@@ -232,6 +554,31 @@ pub mod cigar{
             false
         }
 
+        /// same as get_soft_clipped_n() but sum the soft and hard clips at the strand relevant end
+        /// (3' / last operations for Plus, 5' / first operations for Minus), None if that end is not clipped.
+        #[cfg(feature = "std")]
+        pub fn clip_length_end(&self, strand: &Strand) -> Option<i64>{
+            let is_clip = |op: &&CigarOperation| matches!(op, CigarOperation::Soft(_) | CigarOperation::Hard(_));
+            let add = |acc: Option<i64>, op: &CigarOperation| Some(acc.unwrap_or(0) + op.length());
+            match strand{
+                Strand::Minus => self.cigar.iter().take_while(is_clip).fold(None, add),
+                Strand::Plus => self.cigar.iter().rev().take_while(is_clip).fold(None, add),
+                _ => None
+            }
+        }
+
+        /// return true if the soft clips are placed where the library allows them given the strand:
+        /// for Plus reads only a 3' (last operation) soft clip is allowed, for Minus reads only a 5' (first operation) one.
+        /// hard clips are skipped, "2H5S100M" has a 5' soft clip. always true for Strand::NA.
+        #[cfg(feature = "std")]
+        pub fn clips_consistent_with_strand(&self, strand: &Strand) -> bool{
+            match strand{
+                Strand::Plus => self.leading_soft_clip() == 0,
+                Strand::Minus => self.trailing_soft_clip() == 0,
+                _ => true
+            }
+        }
+
         /// this function return true if the reads fully match region defined by st(art) and end.
         /// inclusive of both end
         /// st >= interval, en <= intervall // TODO make end exclusive
@@ -240,27 +587,32 @@ pub mod cigar{
             let mut ref_pos = *pos;
             let mut flag: bool = false;
             for cigar_op in self.cigar.iter(){
-                match cigar_op{
-                    CigarOperation::Nskipped(n) | CigarOperation::Deletion(n) => {ref_pos += n;},
-                    CigarOperation::Match(n) => {
-                        if (st >= ref_pos) & (end <= ref_pos + n) {
+                let n = cigar_op.length();
+                match cigar_op.class(){
+                    OpClass{ consumes_ref: true, consumes_query: true } => {
+                        // the block covers [ref_pos, ref_pos + n - 1], end is inclusive
+                        if (st >= ref_pos) & (end < ref_pos + n) {
                            flag = true;
                         }
                         ref_pos += n;
                     },
+                    OpClass{ consumes_ref: true, .. } => {ref_pos += n;},
                     _ => (), // does not consme the reference
                 }
             }
             flag
         } 
         
+        /// given the start of the alignment return the end of the alignment on the reference.
+        /// the end is exclusive (start + reference span): "50M100N75M" at 1000 gives 1225, the last aligned base being 1224.
+        /// use reference_last_position() for the inclusive coordinate.
+        /// Note: does_it_match_an_intervall() takes an inclusive end, do not mix both without adjusting by one.
         pub fn get_end_of_aln(&self, pos: &i64) -> i64{
             let mut ref_pos = *pos;
             for cigar_op in self.cigar.iter(){
-                match cigar_op{
-                    CigarOperation::Nskipped(n) | CigarOperation::Deletion(n) | CigarOperation::Match(n)=> {ref_pos += n;},
-                    _ => (), // does not consme the reference
-                }
+                if cigar_op.consume_ref(){
+                    ref_pos += cigar_op.length();
+                } // else does not consme the reference
             }
             ref_pos
         } 
@@ -269,15 +621,16 @@ pub mod cigar{
             let mut ref_pos = st;
             let mut result : Vec<i64> = Vec::new();
             for cigar_op in self.cigar.iter(){
-                match cigar_op{
-                CigarOperation::Nskipped(n) | CigarOperation::Deletion(n) => {
-                        ref_pos += n;
-                    },
-                    CigarOperation::Match(n) =>{
+                let n = cigar_op.length();
+                match cigar_op.class(){
+                    OpClass{ consumes_ref: true, consumes_query: true } =>{
                         result.push(ref_pos);
                         result.push(ref_pos + n);
                         ref_pos += n;
                     },
+                    OpClass{ consumes_ref: true, .. } => {
+                        ref_pos += n;
+                    },
                     _ => ()
                 }
             }
@@ -285,112 +638,2776 @@ pub mod cigar{
         result
     }
 
-        
-    }
+        /// given the start of the alignment, return the (query_start, query_end) offsets (half-open, soft clips included)
+        /// of the bases aligned within the reference window [ref_start, ref_end).
+        /// insertions inside the window extend the query range, deletions and skipped do not.
+        /// return None if no aligned bases fall in the window.
+        pub fn query_range_for_reference(&self, aln_start: i64, ref_start: i64, ref_end: i64) -> Option<(i64, i64)>{
+            let mut ref_pos = aln_start;
+            let mut que_pos = 0;
+            let mut results: Option<(i64, i64)> = None;
 
-    impl fmt::Display for Cigar {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            let mut tobeformatted= Vec::new();
-            for op in &self.cigar{
-                let x = match op{
-                    CigarOperation::Match(length) => format!("{}M", length),
-                    CigarOperation::Insertion(length) => format!("{}I", length),
-                    CigarOperation::Deletion(length) => format!("{}D", length),
-                    CigarOperation::Nskipped(length) => format!("{}N", length),
-                    CigarOperation::Soft(length) => format!("{}S", length),
-                    CigarOperation::Hard(length) => format!("{}H", length),
-                    CigarOperation::Padded(length) => format!("{}P", length),
-                    CigarOperation::Unaligned => format!("*"),
-                    _ => panic!("Invalid CIGAR operation"),
-                };
-                tobeformatted.push(x);
+            for cigar_op in self.cigar.iter(){
+                if ref_pos >= ref_end{
+                    break;
+                }
+                let n = cigar_op.length();
+                if cigar_op.is_aligned(){
+                    let st = ref_pos.max(ref_start);
+                    let en = (ref_pos + n).min(ref_end);
+                    if st < en{
+                        let q_en = que_pos + en - ref_pos;
+                        results = match results{
+                            None => Some((que_pos + st - ref_pos, q_en)),
+                            Some((q_st, _)) => Some((q_st, q_en)),
+                        };
+                    }
+                }
+                if cigar_op.consume_ref(){
+                    ref_pos += n;
+                }
+                if cigar_op.consume_que(){
+                    que_pos += n;
+                    // an insertion (query only, not a clip) inside the window extends the query range
+                    if !cigar_op.consume_ref() && !cigar_op.is_clip(){
+                        results = results.map(|(q_st, _)| (q_st, que_pos));
+                    }
+                }
             }
-            write!(f, "{}", tobeformatted.join(""))
+            results
         }
-    }
 
 
-    #[cfg(test)]
-    mod tests {
-        use crate::cigar::{Cigar, CigarOperation};
-        use super::*;
-        #[test]
-        fn test_from() {
-            let cig = Cigar::from("35M110N45M3I45M10N");
-            assert_eq!(cig, Cigar{ cigar: vec![CigarOperation::Match(35), CigarOperation::Nskipped(110), CigarOperation::Match(45), CigarOperation::Insertion(3),
-            CigarOperation::Match(45), CigarOperation::Nskipped(10)]});
+        /// return the number of operations (not bases) matching the symbol,
+        /// e.g. count_ops('N') is the number of introns, count_ops('I') the number of insertion events.
+        pub fn count_ops(&self, symbol: char) -> usize{
+            self.cigar.iter().filter(|op| op.symbol() == symbol).count()
         }
-        #[test]
-        #[should_panic]
-        fn test_from_panic() {
-            let cig = Cigar::from("35M110N45M3I45M10N50K");
+
+        /// return the slice of the reference spanned by the alignment (through deletions and skipped),
+        /// ref_seq[aln_start..get_end_of_aln(aln_start)].
+        /// the bounds are clamped to the reference so this never panics, an alignment outside the reference gives an empty slice.
+        pub fn reference_footprint<'a>(&self, aln_start: i64, ref_seq: &'a [u8]) -> &'a [u8]{
+            let ref_len = ref_seq.len() as i64;
+            let st = aln_start.clamp(0, ref_len);
+            let en = self.get_end_of_aln(&aln_start).clamp(st, ref_len);
+            &ref_seq[st as usize..en as usize]
         }
-        #[test]
-        fn test_from_str(){
-            let cig = Cigar::from_str("35M110N45M3I45M10N");
-            assert_eq!(cig.unwrap(), Cigar{ cigar: vec![CigarOperation::Match(35), CigarOperation::Nskipped(110), CigarOperation::Match(45), CigarOperation::Insertion(3),
-                CigarOperation::Match(45), CigarOperation::Nskipped(10)]});
+
+        /// rebuild the classic two lines gapped alignment (reference, query) from the cigar and both sequences.
+        /// ref_seq is the full reference (indexed by aln_start), query_seq the read SEQ (soft clipped bases included).
+        /// insertions put '-' in the reference line, deletions and skipped put '-' in the query line.
+        /// soft clipped bases are not shown. bases falling outside the given sequences are shown as 'N'.
+        pub fn to_alignment_strings(&self, aln_start: i64, ref_seq: &[u8], query_seq: &[u8]) -> (String, String){
+            let base = |seq: &[u8], i: i64| -> char {
+                usize::try_from(i).ok().and_then(|i| seq.get(i)).map_or('N', |b| *b as char)
+            };
+            let mut ref_line = String::new();
+            let mut que_line = String::new();
+            let mut ref_pos = aln_start;
+            let mut que_pos = 0;
+
+            for cigar_op in self.cigar.iter(){
+                let n = cigar_op.length();
+                let OpClass{ consumes_ref, consumes_query } = cigar_op.class();
+                // a side that is not consumed gets a gap, clipped bases are not shown
+                if !cigar_op.is_clip() && (consumes_ref || consumes_query){
+                    for i in 0..n{
+                        ref_line.push(if consumes_ref { base(ref_seq, ref_pos + i) } else { '-' });
+                        que_line.push(if consumes_query { base(query_seq, que_pos + i) } else { '-' });
+                    }
+                }
+                if consumes_ref{
+                    ref_pos += n;
+                }
+                if consumes_query{
+                    que_pos += n;
+                }
+            }
+            (ref_line, que_line)
         }
-        #[test]
-        fn test_pos(){
-            let cig = Cigar::from("35M110N45M3I45M10N");
-            let results = cig.get_skipped_pos_on_ref(&500);
-            assert_eq!(results, Some(vec![535, 645, 735, 745]))
+
+
+        /// return a normalized copy of the cigar: zero length operations are dropped
+        /// and adjacent operations of the same type are merged ("10M0I5M" -> "15M").
+        pub fn normalize(&self) -> Cigar{
+            let mut operations: Vec<CigarOperation> = Vec::with_capacity(self.cigar.len());
+            for cigar_op in self.cigar.iter(){
+                if cigar_op.length() == 0{
+                    continue;
+                }
+                match operations.last_mut(){
+                    Some(last) if last.symbol() == cigar_op.symbol() => {
+                        *last = last.with_length(last.length() + cigar_op.length());
+                    },
+                    _ => operations.push(*cigar_op),
+                }
+            }
+            self.debug_check_monotonic(Cigar{ cigar: operations })
+        }
+
+        /// return true if both cigar describe the same alignment once normalized,
+        /// "10M5M" and "15M" are equivalent but not equal.
+        pub fn equivalent(&self, other: &Cigar) -> bool{
+            self.normalize() == other.normalize()
+        }
+
+        /// return the length of the read (query) as described by the cigar,
+        /// soft clipped bases are counted, hard clipped are not (same as the SEQ length).
+        pub fn get_read_length_from_cigar(&self) -> i64{
+            self.cigar.iter()
+            .filter(|op| op.consume_que())
+            .map(|op| op.length())
+            .sum()
+        }
+
+        /// return a copy of the cigar right-padded with a soft clip so its query length equals target_len.
+        /// usefull to give a same width to alignments stacked in a viewer.
+        /// Will return an error if the query is already longer than target_len.
+        pub fn pad_query_to(&self, target_len: i64) -> Result<Cigar, CigarError>{
+            let missing = target_len - self.get_read_length_from_cigar();
+            if missing < 0{
+                return Err(CigarError::QueryTooLong);
+            }
+            let mut padded = self.clone();
+            if missing > 0{
+                // the soft clip goes before a trailing hard clip
+                let mut idx = padded.cigar.len();
+                if let Some(CigarOperation::Hard(_)) = padded.cigar.last(){
+                    idx -= 1;
+                }
+                match idx.checked_sub(1).map(|i| &mut padded.cigar[i]){
+                    Some(CigarOperation::Soft(n)) => { *n += missing; },
+                    _ => padded.cigar.insert(idx, CigarOperation::Soft(missing)),
+                }
+            }
+            Ok(padded)
+        }
+
+        /// given the start of the alignment, return (ref_anchor, insert_len) for each insertion of at least min_len bases.
+        /// ref_anchor is the reference position the insertion is placed before (the reference cursor at the insertion).
+        pub fn large_insertions(&self, aln_start: i64, min_len: i64) -> Vec<(i64, i64)>{
+            let mut ref_pos = aln_start;
+            let mut results = Vec::new();
+            for cigar_op in self.cigar.iter(){
+                match cigar_op{
+                    CigarOperation::Insertion(n) if *n >= min_len => { results.push((ref_pos, *n)); },
+                    op if op.consume_ref() => { ref_pos += op.length(); },
+                    _ => ()
+                }
+            }
+            results
+        }
+
+        /// build a Cigar from a per base operation string, run-length encoding identical consecutive symbols
+        /// ("MMMIIMM" -> "3M2I2M").
+        /// Will return an error if the string contains an unknown symbol.
+        pub fn from_expanded(s: &str) -> Result<Cigar, CigarError>{
+            let mut operations: Vec<CigarOperation> = Vec::new();
+            for c in s.chars(){
+                match operations.last_mut(){
+                    Some(last) if last.symbol() == c => { *last = last.with_length(last.length() + 1); },
+                    _ => {
+                        let op = CigarOperation::from_symbol(c, 1);
+                        if op == CigarOperation::Invalid{
+                            return Err(CigarError::Parse);
+                        }
+                        operations.push(op);
+                    }
+                }
+            }
+            Ok(Cigar{ cigar: operations })
+        }
+
+        /// return the length of the longest aligned block (M) on the reference, 0 if there is none.
+        /// a read whose longest anchor is short is likely a poor alignment.
+        pub fn longest_match_ref(&self) -> i64{
+            self.cigar.iter()
+            .filter(|op| op.is_aligned())
+            .map(|op| op.length())
+            .max()
+            .unwrap_or(0)
+        }
+
+        /// return the length of the longest aligned block (M) on the query, 0 if there is none.
+        /// equal to longest_match_ref() as aligned blocks consume both sequences the same way.
+        pub fn longest_match_query(&self) -> i64{
+            self.longest_match_ref()
+        }
+
+        /// given the start of the alignment, return for each intron (N) the reference coordinates of its
+        /// donor and acceptor dinucleotides: (donor_pos1, donor_pos2, acceptor_pos1, acceptor_pos2).
+        /// for an intron covering [start, end) (as given by get_skipped_pos_on_ref) this is (start, start + 1, end - 2, end - 1),
+        /// so ref_seq[donor_pos1] ref_seq[donor_pos2] ... ref_seq[acceptor_pos1] ref_seq[acceptor_pos2] reads GT...AG.
+        pub fn intron_motif_coordinates(&self, aln_start: i64) -> Vec<(i64, i64, i64, i64)>{
+            match self.get_skipped_pos_on_ref(&aln_start){
+                Some(junctions) => junctions.chunks(2)
+                    .map(|j| (j[0], j[0] + 1, j[1] - 2, j[1] - 1))
+                    .collect(),
+                None => Vec::new(),
+            }
+        }
+
+        /// consume the Cigar and return its operations.
+        pub fn into_operations(self) -> Vec<CigarOperation>{
+            self.cigar
+        }
+
+        /// return a copy of the cigar truncated after max_query_len query bases.
+        /// the remaining query bases become a single trailing soft clip and the reference only operations (D, N) past
+        /// the truncation point are dropped, a trailing hard clip is kept.
+        /// if max_query_len is larger than the read the cigar is returned unchanged.
+        pub fn truncate_query(&self, max_query_len: i64) -> Cigar{
+            let read_len = self.get_read_length_from_cigar();
+            if max_query_len >= read_len{
+                return self.clone();
+            }
+            let mut operations: Vec<CigarOperation> = Vec::with_capacity(self.cigar.len() + 1);
+            let leading_hard = self.cigar.iter().take_while(|op| matches!(op, CigarOperation::Hard(_))).count();
+            operations.extend_from_slice(&self.cigar[..leading_hard]);
+            let mut que_pos = 0;
+            for cigar_op in self.cigar[leading_hard..].iter(){
+                if que_pos >= max_query_len{
+                    break;
+                }
+                if cigar_op.consume_que(){
+                    let taken = cigar_op.length().min(max_query_len - que_pos);
+                    operations.push(cigar_op.with_length(taken));
+                    que_pos += taken;
+                }
+                else if !matches!(cigar_op, CigarOperation::Hard(_)){
+                    operations.push(*cigar_op);
+                }
+            }
+            let clipped = read_len - que_pos;
+            match operations.last_mut(){
+                Some(CigarOperation::Soft(n)) => { *n += clipped; },
+                _ => operations.push(CigarOperation::Soft(clipped)),
+            }
+            if let Some(CigarOperation::Hard(n)) = self.cigar.last(){
+                operations.push(CigarOperation::Hard(*n));
+            }
+            self.debug_check_monotonic(Cigar{ cigar: operations })
+        }
+
+        /// given the start of the alignment, lazily yield the reference range [start, end) of each aligned (M) operation.
+        fn aligned_ref_blocks(&self, aln_start: i64) -> impl Iterator<Item = Range<i64>> + '_{
+            let mut ref_pos = aln_start;
+            self.cigar.iter()
+            .filter_map(move |cigar_op| {
+                let block_start = ref_pos;
+                if cigar_op.consume_ref(){
+                    ref_pos += cigar_op.length();
+                }
+                if cigar_op.is_aligned(){
+                    Some(block_start..ref_pos)
+                }
+                else{
+                    None
+                }
+            })
+        }
+
+        /// given the start of the alignment, lazily yield the aligned (M) reference positions falling in [win_start, win_end).
+        /// positions outside the window are never materialized.
+        pub fn aligned_positions_in_window(&self, aln_start: i64, win_start: i64, win_end: i64) -> impl Iterator<Item = i64> + '_{
+            self.aligned_ref_blocks(aln_start)
+            .flat_map(move |block| block.start.max(win_start)..block.end.min(win_end))
+        }
+
+        /// return true if the reference base at ref_pos and the query base at que_pos are known and differ (case insensitive),
+        /// a base falling outside its sequence cannot be compared and is never a mismatch.
+        fn bases_differ(ref_seq: &[u8], query_seq: &[u8], ref_pos: i64, que_pos: i64) -> bool{
+            let base = |seq: &[u8], i: i64| -> Option<u8> {
+                usize::try_from(i).ok().and_then(|i| seq.get(i)).copied()
+            };
+            match (base(ref_seq, ref_pos), base(query_seq, que_pos)){
+                (Some(r), Some(q)) => !r.eq_ignore_ascii_case(&q),
+                _ => false,
+            }
+        }
+
+        /// return the total length of the operations matching the symbol.
+        fn sum_ops(&self, symbol: char) -> i64{
+            self.cigar.iter()
+            .filter(|op| op.symbol() == symbol)
+            .map(|op| op.length())
+            .sum()
+        }
+
+        /// return a one line human readable summary of the cigar, usefull for logging
+        /// e.g. "150bp read, 2 introns, 12bp soft-clipped, 1 deletion (3bp)".
+        /// the read length is always reported, the other parts only when not zero.
+        pub fn describe(&self) -> String{
+            let mut description = format!("{}bp read", self.get_read_length_from_cigar());
+            let plural = |n: usize| if n > 1 { "s" } else { "" };
+
+            let introns = self.count_ops('N');
+            if introns > 0{
+                let _ = write!(description, ", {} intron{}", introns, plural(introns));
+            }
+            for (symbol, name) in [('S', "soft"), ('H', "hard")]{
+                let clipped = self.sum_ops(symbol);
+                if clipped > 0{
+                    let _ = write!(description, ", {}bp {}-clipped", clipped, name);
+                }
+            }
+            for (symbol, name) in [('D', "deletion"), ('I', "insertion")]{
+                let events = self.count_ops(symbol);
+                if events > 0{
+                    let _ = write!(description, ", {} {}{} ({}bp)", events, name, plural(events), self.sum_ops(symbol));
+                }
+            }
+            description
+        }
+
+        /// given the start of the alignment, increment coverage[pos - region_start] for each aligned (M) reference position
+        /// falling in [region_start, region_start + coverage.len()).
+        /// does not allocate, meant to build a pileup over many reads with a single buffer.
+        pub fn add_to_coverage(&self, aln_start: i64, region_start: i64, coverage: &mut [u32]){
+            let region_end = region_start + coverage.len() as i64;
+            for pos in self.aligned_positions_in_window(aln_start, region_start, region_end){
+                coverage[(pos - region_start) as usize] += 1;
+            }
+        }
+
+        /// given the start of the alignment, return true if ref_pos is inside an aligned (M) block
+        /// with at least min_flank matched bases before and after it in that same block.
+        pub fn covers_with_flank(&self, aln_start: i64, ref_pos: i64, min_flank: i64) -> bool{
+            let mut block_start = aln_start;
+            for cigar_op in self.cigar.iter(){
+                let block_end = block_start + if cigar_op.consume_ref() { cigar_op.length() } else { 0 };
+                if cigar_op.is_aligned() && (block_start..block_end).contains(&ref_pos){
+                    return (ref_pos - block_start >= min_flank) & (block_end - 1 - ref_pos >= min_flank);
+                }
+                if block_start > ref_pos{
+                    break;
+                }
+                block_start = block_end;
+            }
+            false
+        }
+
+        /// return the cumulative query length after each operation, operations not consuming the query repeat the previous offset.
+        /// the query bases of operation i are read[boundaries[i - 1]..boundaries[i]] (starting at 0 for the first one).
+        pub fn query_boundaries(&self) -> Vec<i64>{
+            let mut que_pos = 0;
+            self.cigar.iter()
+            .map(|cigar_op| {
+                if cigar_op.consume_que(){
+                    que_pos += cigar_op.length();
+                }
+                que_pos
+            })
+            .collect()
+        }
+
+        /// given the start of the alignment, the reference and the read SEQ (soft clipped bases included),
+        /// return each insertion and deletion as a VCF-like (pos, ref_allele, alt_allele) record,
+        /// left-anchored on the preceding reference base: pos is the (0-based) coordinate of that anchor base.
+        /// indels whose bases fall outside the given sequences (e.g. an indel at position 0) are skipped.
+        pub fn indels_as_alleles(&self, aln_start: i64, ref_seq: &[u8], query_seq: &[u8]) -> Vec<(i64, Vec<u8>, Vec<u8>)>{
+            let slice = |seq: &[u8], st: i64, en: i64| -> Option<Vec<u8>> {
+                let st = usize::try_from(st).ok()?;
+                let en = usize::try_from(en).ok()?;
+                seq.get(st..en).map(|s| s.to_vec())
+            };
+            let mut ref_pos = aln_start;
+            let mut que_pos = 0;
+            let mut results = Vec::new();
+
+            for cigar_op in self.cigar.iter(){
+                let n = cigar_op.length();
+                match cigar_op{
+                    CigarOperation::Insertion(_) => {
+                        if let (Some(anchor), Some(inserted)) = (slice(ref_seq, ref_pos - 1, ref_pos), slice(query_seq, que_pos, que_pos + n)){
+                            let mut alt = anchor.clone();
+                            alt.extend(inserted);
+                            results.push((ref_pos - 1, anchor, alt));
+                        }
+                    },
+                    CigarOperation::Deletion(_) => {
+                        if let Some(deleted) = slice(ref_seq, ref_pos - 1, ref_pos + n){
+                            let alt = deleted[..1].to_vec();
+                            results.push((ref_pos - 1, deleted, alt));
+                        }
+                    },
+                    _ => ()
+                }
+                if cigar_op.consume_ref(){
+                    ref_pos += n;
+                }
+                if cigar_op.consume_que(){
+                    que_pos += n;
+                }
+            }
+            results
+        }
+
+        /// return a normalized copy of the cigar where deletions of at most max_gap bases are turned into matches,
+        /// merging them with the flanking aligned blocks ("10M2D10M" -> "22M" for max_gap >= 2).
+        pub fn bridge_short_deletions(&self, max_gap: i64) -> Cigar{
+            let operations: Vec<CigarOperation> = self.cigar.iter()
+            .map(|cigar_op| match cigar_op{
+                CigarOperation::Deletion(n) if *n <= max_gap => CigarOperation::Match(*n),
+                op => *op
+            })
+            .collect();
+            Cigar{ cigar: operations }.normalize()
+        }
+
+        /// given the start of the alignment return the last reference position covered by the alignment (inclusive),
+        /// that is get_end_of_aln() - 1.
+        pub fn reference_last_position(&self, aln_start: i64) -> i64{
+            self.get_end_of_aln(&aln_start) - 1
+        }
+
+        /// return the length of the soft clip at the start of the cigar (after a hard clip if any), 0 if none.
+        fn leading_soft_clip(&self) -> i64{
+            match self.cigar.iter().find(|op| !matches!(op, CigarOperation::Hard(_))){
+                Some(CigarOperation::Soft(n)) => *n,
+                _ => 0
+            }
+        }
+
+        /// return the length of the soft clip at the end of the cigar (before a hard clip if any), 0 if none.
+        fn trailing_soft_clip(&self) -> i64{
+            match self.cigar.iter().rev().find(|op| !matches!(op, CigarOperation::Hard(_))){
+                Some(CigarOperation::Soft(n)) => *n,
+                _ => 0
+            }
+        }
+
+        /// given the start of the alignment return the reference position of the first sequenced base,
+        /// aln_start minus the leading soft clip (hard clipped bases are not counted).
+        pub fn unclipped_start(&self, aln_start: i64) -> i64{
+            aln_start - self.leading_soft_clip()
+        }
+
+        /// given the start of the alignment return the (exclusive) reference end including the trailing soft clip,
+        /// get_end_of_aln() plus the trailing soft clip (hard clipped bases are not counted).
+        pub fn unclipped_end(&self, aln_start: i64) -> i64{
+            self.get_end_of_aln(&aln_start) + self.trailing_soft_clip()
+        }
+
+        /// iterate over the operations from the last one (3' end of the cigar) to the first one, without allocation.
+        /// the iterator is double ended so .rev() walks back in the cigar order.
+        pub fn iter_rev(&self) -> impl DoubleEndedIterator<Item = &CigarOperation> + ExactSizeIterator{
+            self.cigar.iter().rev()
+        }
+
+        /// same as get_end_of_aln() but return None if the computation overflows i64,
+        /// which can only happen with a corrupted cigar (or start), so defensive callers can detect it.
+        pub fn checked_reference_end(&self, aln_start: i64) -> Option<i64>{
+            self.cigar.iter()
+            .filter(|op| op.consume_ref())
+            .try_fold(aln_start, |ref_pos, op| ref_pos.checked_add(op.length()))
+        }
+
+        /// return the (5', 3') soft clip lengths regardless of the strand, 0 when an end is not soft clipped.
+        /// the strand agnostic companion of get_soft_clipped_n(), a hard clip outside the soft clip is skipped.
+        pub fn soft_clips(&self) -> (i64, i64){
+            (self.leading_soft_clip(), self.trailing_soft_clip())
+        }
+
+        /// given the query coordinate of the first operation, return the operation whose query span contains query_pos
+        /// (soft clip, insertion or aligned block), None if query_pos is outside the read.
+        pub fn op_at_query(&self, query_start: i64, query_pos: i64) -> Option<&CigarOperation>{
+            let mut que_pos = query_start;
+            for cigar_op in self.cigar.iter().filter(|op| op.consume_que()){
+                que_pos += cigar_op.length();
+                if query_pos < que_pos{
+                    return if query_pos >= que_pos - cigar_op.length() { Some(cigar_op) } else { None };
+                }
+            }
+            None
+        }
+
+        /// return a summary of the alignment (query length, reference span, junction count, clips and indels).
+        pub fn summary(&self) -> CigarSummary{
+            CigarSummary{
+                query_length: self.get_read_length_from_cigar(),
+                reference_span: self.get_end_of_aln(&0),
+                junction_count: self.count_ops('N'),
+                soft_clipped: self.sum_ops('S'),
+                hard_clipped: self.sum_ops('H'),
+                insertion_count: self.count_ops('I'),
+                insertion_bases: self.sum_ops('I'),
+                deletion_count: self.count_ops('D'),
+                deletion_bases: self.sum_ops('D'),
+            }
+        }
+
+        /// return summary() as a JSON object.
+        #[cfg(feature = "serde")]
+        pub fn summary_json(&self) -> String{
+            serde_json::to_string(&self.summary()).expect("CigarSummary is always serializable")
+        }
+
+        /// return a copy of the cigar where the soft clip at which_end is realigned across an intron of intron_len bases:
+        /// "100M45S" with End::Three gives "100M{intron_len}N45M", "45S100M" with End::Five gives "45M{intron_len}N100M".
+        /// the query length is unchanged, for End::Five the alignment now starts 45 + intron_len bases upstream,
+        /// the caller has to shift the alignment start accordingly.
+        /// the cigar is returned unchanged if there is no soft clip at that end.
+        pub fn soft_clip_to_intron(&self, which_end: End, intron_len: i64) -> Cigar{
+            let is_hard = |op: &&CigarOperation| matches!(op, CigarOperation::Hard(_));
+            let idx = match which_end{
+                End::Five => self.cigar.iter().take_while(is_hard).count(),
+                End::Three => self.cigar.len().saturating_sub(1 + self.cigar.iter().rev().take_while(is_hard).count()),
+            };
+            let mut operations = self.cigar.clone();
+            if let Some(CigarOperation::Soft(n)) = self.cigar.get(idx){
+                let realigned = match which_end{
+                    End::Five => [CigarOperation::Match(*n), CigarOperation::Nskipped(intron_len)],
+                    End::Three => [CigarOperation::Nskipped(intron_len), CigarOperation::Match(*n)],
+                };
+                operations.splice(idx..idx + 1, realigned);
+            }
+            Cigar{ cigar: operations }
+        }
+
+        /// given the start of the alignment, return the number of aligned (M) reference bases falling in [win_start, win_end),
+        /// blocks partially overlapping the window only count their overlap.
+        pub fn aligned_bases_in_window(&self, aln_start: i64, win_start: i64, win_end: i64) -> i64{
+            self.aligned_ref_blocks(aln_start)
+            .map(|block| (block.end.min(win_end) - block.start.max(win_start)).max(0))
+            .sum()
+        }
+
+        /// compare the operations of self (the expected cigar, e.g. a truth set) with other, position by position,
+        /// and return each divergence. an empty vector means both cigars are equal.
+        /// no normalization is done, use normalize() first to compare alignments shapes.
+        pub fn diff(&self, other: &Cigar) -> Vec<CigarDiff>{
+            let mut results = Vec::new();
+            for index in 0..self.cigar.len().max(other.cigar.len()){
+                match (self.cigar.get(index), other.cigar.get(index)){
+                    (Some(expected), Some(got)) if expected != got => {
+                        results.push(CigarDiff::Mismatch{ index, expected: *expected, got: *got });
+                    },
+                    (Some(expected), None) => results.push(CigarDiff::Missing{ index, expected: *expected }),
+                    (None, Some(got)) => results.push(CigarDiff::Extra{ index, got: *got }),
+                    _ => ()
+                }
+            }
+            results
+        }
+
+        /// given the start of the alignment on the reference and on the query, map the reference interval [ref_start, ref_end)
+        /// to the paired (ref_range, query_range) segments covering it, in order.
+        /// aligned blocks give both ranges, deletions and skipped give an empty query range (q..q)
+        /// and insertions between two positions of the interval give an empty reference range (r..r).
+        pub fn map_reference_interval(&self, aln_start: i64, query_start: i64, ref_start: i64, ref_end: i64) -> Vec<(Range<i64>, Range<i64>)>{
+            let mut ref_pos = aln_start;
+            let mut que_pos = query_start;
+            let mut results = Vec::new();
+
+            for cigar_op in self.cigar.iter(){
+                if ref_pos >= ref_end{
+                    break;
+                }
+                let n = cigar_op.length();
+                let class = cigar_op.class();
+                if class.consumes_ref{
+                    let st = ref_pos.max(ref_start);
+                    let en = (ref_pos + n).min(ref_end);
+                    if st < en{
+                        let query_range = if class.consumes_query{
+                            que_pos + st - ref_pos..que_pos + en - ref_pos
+                        } else { que_pos..que_pos };
+                        results.push((st..en, query_range));
+                    }
+                }
+                else if matches!(cigar_op, CigarOperation::Insertion(_)) && ref_pos > ref_start{
+                    results.push((ref_pos..ref_pos, que_pos..que_pos + n));
+                }
+                if class.consumes_ref{
+                    ref_pos += n;
+                }
+                if class.consumes_query{
+                    que_pos += n;
+                }
+            }
+            results
+        }
+
+        /// return the index range of the operations between the leading and trailing clips (S and H).
+        fn unclipped_range(&self) -> Range<usize>{
+            let is_clip = |op: &&CigarOperation| matches!(op, CigarOperation::Soft(_) | CigarOperation::Hard(_));
+            let st = self.cigar.iter().take_while(is_clip).count();
+            let en = self.cigar.len() - self.cigar[st..].iter().rev().take_while(is_clip).count();
+            st..en
+        }
+
+        /// return true if the first or last operation (clips apart) is a deletion, which is not a valid alignment.
+        pub fn has_terminal_deletion(&self) -> bool{
+            let core = &self.cigar[self.unclipped_range()];
+            matches!(core.first(), Some(CigarOperation::Deletion(_))) || matches!(core.last(), Some(CigarOperation::Deletion(_)))
+        }
+
+        /// return a copy of the cigar without its leading and trailing deletions (clips apart),
+        /// together with the shift to apply to the alignment start: the length of the removed leading deletions.
+        pub fn trim_terminal_deletions(&self) -> (Cigar, i64){
+            let range = self.unclipped_range();
+            let core = &self.cigar[range.clone()];
+            let is_del = |op: &&CigarOperation| matches!(op, CigarOperation::Deletion(_));
+            let leading = core.iter().take_while(is_del).count();
+            let trailing = core[leading..].iter().rev().take_while(is_del).count();
+            let start_shift = core[..leading].iter().map(|op| op.length()).sum();
+
+            let mut operations = Vec::with_capacity(self.cigar.len() - leading - trailing);
+            operations.extend_from_slice(&self.cigar[..range.start]);
+            operations.extend_from_slice(&core[leading..core.len() - trailing]);
+            operations.extend_from_slice(&self.cigar[range.end..]);
+            (self.debug_check_monotonic(Cigar{ cigar: operations }), start_shift)
+        }
+
+        /// return the cigar in the BAM binary encoding, one u32 per operation: length << 4 | op code.
+        /// lengths are expected to fit in 28 bits as required by the BAM format.
+        pub fn to_bam_u32(&self) -> Vec<u32>{
+            self.cigar.iter()
+            .filter_map(|op| op.bam_code().map(|code| (op.length() as u32) << 4 | code))
+            .collect()
+        }
+
+        /// build a Cigar from the BAM binary encoding (length << 4 | op code per operation), the inverse of to_bam_u32().
+        /// Will return an error on op codes larger than 8.
+        pub fn from_bam_u32(ops: &[u32]) -> Result<Cigar, CigarError>{
+            let mut operations = Vec::with_capacity(ops.len());
+            for op in ops{
+                let symbol = match "MIDNSHP=X".chars().nth((op & 0xf) as usize){
+                    Some(symbol) => symbol,
+                    None => return Err(CigarError::InvalidOpCode),
+                };
+                operations.push(CigarOperation::from_symbol(symbol, (op >> 4) as i64));
+            }
+            Ok(Cigar{ cigar: operations })
+        }
+
+        /// given the start of the alignment, return the reference ranges of the aligned segments where consecutive
+        /// aligned (M) blocks separated by a reference gap (D, N) of at most max_internal_gap bases are merged,
+        /// larger gaps (typically introns) start a new segment.
+        pub fn segments(&self, aln_start: i64, max_internal_gap: i64) -> Vec<Range<i64>>{
+            let mut results: Vec<Range<i64>> = Vec::new();
+            for block in self.aligned_ref_blocks(aln_start){
+                match results.last_mut(){
+                    Some(last) if block.start - last.end <= max_internal_gap => { last.end = block.end; },
+                    _ => results.push(block),
+                }
+            }
+            results
+        }
+
+        /// check that the alignment fits the sequences it was loaded with: aln_start >= 0,
+        /// get_end_of_aln() <= ref_len and the query length of the cigar equals query_len.
+        /// Will return the first failing check as an error.
+        pub fn validate_dimensions(&self, aln_start: i64, ref_len: i64, query_len: i64) -> Result<(), CigarError>{
+            if aln_start < 0{
+                return Err(CigarError::NegativeStart(aln_start));
+            }
+            let end = self.get_end_of_aln(&aln_start);
+            if end > ref_len{
+                return Err(CigarError::BeyondReference{ end, ref_len });
+            }
+            let cigar_len = self.get_read_length_from_cigar();
+            if cigar_len != query_len{
+                return Err(CigarError::QueryLengthMismatch{ cigar: cigar_len, query: query_len });
+            }
+            Ok(())
+        }
+
+        /// return a copy of the cigar with the operations in reverse order.
+        pub fn reverse(&self) -> Cigar{
+            self.debug_check_monotonic(Cigar{ cigar: self.cigar.iter().rev().copied().collect() })
+        }
+
+        /// given the start of the alignment and the reference length, return the cigar and start of the alignment
+        /// on the opposite strand coordinate system: the reversed cigar and ref_len - get_end_of_aln(aln_start).
+        pub fn to_minus_strand(&self, aln_start: i64, ref_len: i64) -> (Cigar, i64){
+            (self.reverse(), ref_len - self.get_end_of_aln(&aln_start))
+        }
+
+        /// return the total number of bases per operation type keyed by symbol, e.g. counts[&'N'] is the total intronic length.
+        /// an unaligned cigar gives an empty map.
+        #[cfg(feature = "std")]
+        pub fn base_counts_by_op(&self) -> std::collections::HashMap<char, i64>{
+            let mut counts = std::collections::HashMap::new();
+            for cigar_op in self.cigar.iter().filter(|op| op.bam_code().is_some()){
+                *counts.entry(cigar_op.symbol()).or_insert(0) += cigar_op.length();
+            }
+            counts
+        }
+
+        /// given the start of the alignment, return true if the read supports the junction [donor, acceptor):
+        /// a skipped (N) operation spans exactly these coordinates (as given by get_skipped_pos_on_ref)
+        /// and is immediately flanked by aligned (M) blocks on both sides.
+        pub fn supports_junction(&self, aln_start: i64, donor: i64, acceptor: i64) -> bool{
+            let mut ref_pos = aln_start;
+            for (i, cigar_op) in self.cigar.iter().enumerate(){
+                if let CigarOperation::Nskipped(n) = cigar_op{
+                    if ref_pos == donor && ref_pos + n == acceptor{
+                        let flanked = |idx: Option<usize>| idx.and_then(|idx| self.cigar.get(idx)).is_some_and(|op| op.is_aligned());
+                        return flanked(i.checked_sub(1)) && flanked(Some(i + 1));
+                    }
+                }
+                if ref_pos > donor{
+                    break;
+                }
+                if cigar_op.consume_ref(){
+                    ref_pos += cigar_op.length();
+                }
+            }
+            false
+        }
+
+        /// return true if the reference cursor never moves backward, i.e. no reference consuming operation has a negative length.
+        /// always true for a parsed cigar, meaningful as a post-condition after building or editing operations by hand.
+        pub fn is_monotonic_reference(&self) -> bool{
+            self.cigar.iter().all(|op| !op.consume_ref() || op.length() >= 0)
+        }
+
+        /// debug check used by the manipulation methods: a monotonic cigar must give a monotonic result.
+        fn debug_check_monotonic(&self, result: Cigar) -> Cigar{
+            debug_assert!(!self.is_monotonic_reference() || result.is_monotonic_reference(),
+                "cigar manipulation moved the reference backward: {} -> {}", self, result);
+            result
+        }
+
+        /// given the start of the alignment, return the sub-cigar covering the reference [aln_start, up_to_ref).
+        /// a block straddling up_to_ref is split (a split deletion or skipped is kept, so the reference span is exact),
+        /// the remaining query bases become a trailing soft clip and a trailing hard clip is kept.
+        /// the alignment start is unchanged. if up_to_ref is at or past the end of the alignment the cigar is returned unchanged.
+        pub fn reference_prefix(&self, aln_start: i64, up_to_ref: i64) -> Cigar{
+            if self.get_end_of_aln(&aln_start) <= up_to_ref{
+                return self.clone();
+            }
+            let mut operations: Vec<CigarOperation> = Vec::with_capacity(self.cigar.len() + 1);
+            let mut ref_pos = aln_start;
+            let mut que_pos = 0;
+            let core_start = self.unclipped_range().start;
+
+            for (i, cigar_op) in self.cigar.iter().enumerate(){
+                if ref_pos >= up_to_ref && i >= core_start{
+                    break;
+                }
+                let mut n = cigar_op.length();
+                if cigar_op.consume_ref(){
+                    n = n.min(up_to_ref - ref_pos);
+                    ref_pos += n;
+                }
+                if cigar_op.consume_que(){
+                    que_pos += n;
+                }
+                operations.push(cigar_op.with_length(n));
+            }
+
+            let clipped = self.get_read_length_from_cigar() - que_pos;
+            if clipped > 0{
+                match operations.last_mut(){
+                    Some(CigarOperation::Soft(n)) => { *n += clipped; },
+                    _ => operations.push(CigarOperation::Soft(clipped)),
+                }
+            }
+            if let Some(CigarOperation::Hard(n)) = self.cigar.last(){
+                operations.push(CigarOperation::Hard(*n));
+            }
+            self.debug_check_monotonic(Cigar{ cigar: operations })
+        }
+
+        /// return the sub-cigar covering the first up_to_query query bases (see truncate_query())
+        /// together with the number of reference bases it consumes.
+        pub fn query_prefix(&self, up_to_query: i64) -> (Cigar, i64){
+            let prefix = self.truncate_query(up_to_query);
+            let reference_consumed = prefix.get_end_of_aln(&0);
+            (prefix, reference_consumed)
+        }
+
+        /// append the cigar string to buf without allocating a new String (buf is not cleared),
+        /// usefull to format many cigars with a single reused buffer.
+        pub fn write_to(&self, buf: &mut String){
+            let _ = write!(buf, "{}", self);
+        }
+
+        /// return the number of operations per type in an array indexed by BAM op code (M=0, I=1, D=2, N=3, S=4, H=5, P=6, ==7, X=8).
+        /// allocation free, suited to accumulate histograms over a whole BAM.
+        pub fn op_count_array(&self) -> [usize; 9]{
+            let mut counts = [0; 9];
+            for code in self.cigar.iter().filter_map(|op| op.bam_code()){
+                counts[code as usize] += 1;
+            }
+            counts
+        }
+
+        /// return the number of bases per operation type in an array indexed by BAM op code, see op_count_array().
+        pub fn base_count_array(&self) -> [i64; 9]{
+            let mut counts = [0; 9];
+            for op in self.cigar.iter(){
+                if let Some(code) = op.bam_code(){
+                    counts[code as usize] += op.length();
+                }
+            }
+            counts
+        }
+
+        /// return a normalized copy of the cigar where every skipped (N) operation becomes a deletion,
+        /// to compare a spliced alignment with a genomic one using a single gap vocabulary.
+        pub fn introns_as_deletions(&self) -> Cigar{
+            let operations: Vec<CigarOperation> = self.cigar.iter()
+            .map(|cigar_op| match cigar_op{
+                CigarOperation::Nskipped(n) => CigarOperation::Deletion(*n),
+                op => *op
+            })
+            .collect();
+            Cigar{ cigar: operations }.normalize()
+        }
+
+        /// given the start of the alignment, return the reference position of the first aligned (M) base,
+        /// skipping leading clips and any leading D/N of a malformed cigar. None if there is no aligned block.
+        pub fn first_aligned_reference_position(&self, aln_start: i64) -> Option<i64>{
+            self.aligned_ref_blocks(aln_start).next().map(|block| block.start)
+        }
+
+        /// given the start of the alignment, return the (exclusive) reference end of the last aligned (M) block,
+        /// ignoring trailing D/N, so it can be smaller than get_end_of_aln(). None if there is no aligned block.
+        /// together with first_aligned_reference_position() it gives the aligned span without terminal gaps.
+        pub fn last_aligned_reference_position(&self, aln_start: i64) -> Option<i64>{
+            self.aligned_ref_blocks(aln_start).last().map(|block| block.end)
+        }
+
+        /// return the concatenation of self and other, merging the two operations at the junction if they are of the same type
+        /// ("10M" + "5M2I" -> "15M2I"). nothing else is normalized.
+        /// the empty cigar (Cigar::default()) is the identity and concat is associative,
+        /// so chunks can be assembled with fold(Cigar::default(), |a, b| a.concat(&b)).
+        pub fn concat(&self, other: &Cigar) -> Cigar{
+            let mut operations = Vec::with_capacity(self.cigar.len() + other.cigar.len());
+            operations.extend_from_slice(&self.cigar);
+            let mut rest = other.cigar.iter();
+            if let (Some(last), Some(first)) = (operations.last_mut(), other.cigar.first()){
+                if last.symbol() == first.symbol(){
+                    *last = last.with_length(last.length() + first.length());
+                    rest.next();
+                }
+            }
+            operations.extend(rest);
+            let result = Cigar{ cigar: operations };
+            debug_assert!(!(self.is_monotonic_reference() && other.is_monotonic_reference()) || result.is_monotonic_reference(),
+                "cigar concatenation moved the reference backward: {} + {} -> {}", self, other, result);
+            result
+        }
+
+        /// given the start of the alignment, return the part of the alignment falling in the reference window [win_start, win_end):
+        /// the sub-cigar (trimmed so it starts and ends with an aligned block), its reference start
+        /// and the query offsets (soft clips included) to slice SEQ and QUAL with.
+        /// return None if no aligned base falls in the window.
+        pub fn restrict_to_window(&self, aln_start: i64, win_start: i64, win_end: i64) -> Option<WindowSlice>{
+            // (operation, reference start, query start, query end) of each operation overlapping the window
+            let mut parts: Vec<(CigarOperation, i64, i64, i64)> = Vec::new();
+            let mut ref_pos = aln_start;
+            let mut que_pos = 0;
+
+            for cigar_op in self.cigar.iter(){
+                if ref_pos >= win_end{
+                    break;
+                }
+                let n = cigar_op.length();
+                let class = cigar_op.class();
+                if class.consumes_ref{
+                    let st = ref_pos.max(win_start);
+                    let en = (ref_pos + n).min(win_end);
+                    if st < en{
+                        let q_st = if class.consumes_query { que_pos + st - ref_pos } else { que_pos };
+                        let q_en = if class.consumes_query { que_pos + en - ref_pos } else { que_pos };
+                        parts.push((cigar_op.with_length(en - st), st, q_st, q_en));
+                    }
+                    ref_pos += n;
+                }
+                else if matches!(cigar_op, CigarOperation::Insertion(_)) && ref_pos > win_start{
+                    parts.push((*cigar_op, ref_pos, que_pos, que_pos + n));
+                }
+                if class.consumes_query{
+                    que_pos += n;
+                }
+            }
+
+            let first = parts.iter().position(|part| part.0.is_aligned())?;
+            let last = parts.iter().rposition(|part| part.0.is_aligned())?;
+            let parts = &parts[first..=last];
+            Some(WindowSlice{
+                cigar: Cigar{ cigar: parts.iter().map(|part| part.0).collect() },
+                new_ref_start: parts[0].1,
+                query_start: parts[0].2,
+                query_end: parts[parts.len() - 1].3,
+            })
+        }
+
+        /// given the start of the alignment, return the sorted and deduplicated indices (ref_pos / bin_size) of the
+        /// bins containing at least one aligned (M) base. bins only spanned by an intron or a deletion are not reported.
+        /// a bin_size of 0 or less gives an empty Vec.
+        pub fn covered_bins(&self, aln_start: i64, bin_size: i64) -> Vec<i64>{
+            let mut results: Vec<i64> = Vec::new();
+            if bin_size <= 0{
+                return results;
+            }
+            for block in self.aligned_ref_blocks(aln_start).filter(|block| !block.is_empty()){
+                let first = block.start.div_euclid(bin_size);
+                let last = (block.end - 1).div_euclid(bin_size);
+                let first = match results.last(){
+                    Some(previous) if *previous >= first => previous + 1,
+                    _ => first,
+                };
+                results.extend(first..=last);
+            }
+            results
+        }
+
+        /// given the query sequence (SEQ), return the 5' and 3' soft clipped subsequences,
+        /// None where there is no soft clip (or if the sequence is too short for the clip).
+        pub fn soft_clipped_sequences<'a>(&self, query_seq: &'a [u8]) -> (Option<&'a [u8]>, Option<&'a [u8]>){
+            let five = self.leading_soft_clip() as usize;
+            let three = self.trailing_soft_clip() as usize;
+            let five_seq = if five > 0 { query_seq.get(..five) } else { None };
+            let three_seq = match query_seq.len().checked_sub(three){
+                Some(start) if three > 0 => query_seq.get(start..),
+                _ => None,
+            };
+            (five_seq, three_seq)
+        }
+
+        /// given the start of the alignment on the reference and on the query, return (query_pos, ref_anchor, insert_len) for each insertion.
+        /// query_pos is the first inserted base on the query, ref_anchor is the reference position the insertion is placed before.
+        pub fn insertions_query_positions(&self, aln_start: i64, query_start: i64) -> Vec<(i64, i64, i64)>{
+            let mut ref_pos = aln_start;
+            let mut que_pos = query_start;
+            let mut results = Vec::new();
+            for cigar_op in self.cigar.iter(){
+                if let CigarOperation::Insertion(n) = cigar_op{
+                    results.push((que_pos, ref_pos, *n));
+                }
+                if cigar_op.consume_ref(){
+                    ref_pos += cigar_op.length();
+                }
+                if cigar_op.consume_que(){
+                    que_pos += cigar_op.length();
+                }
+            }
+            results
+        }
+
+        /// given the start of the alignment on the reference and on the query, the full reference (indexed by aln_start)
+        /// and the read SEQ (indexed by query_start), return the number of aligned (M) bases that differ between both sequences.
+        /// the comparison is case insensitive, bases falling outside the given sequences are not counted.
+        pub fn count_mismatches(&self, aln_start: i64, ref_seq: &[u8], query_seq: &[u8], query_start: i64) -> i64{
+            let mut ref_pos = aln_start;
+            let mut que_pos = query_start;
+            let mut mismatches = 0;
+            for cigar_op in self.cigar.iter(){
+                let n = cigar_op.length();
+                if cigar_op.is_aligned(){
+                    mismatches += (0..n).filter(|i| Cigar::bases_differ(ref_seq, query_seq, ref_pos + i, que_pos + i)).count() as i64;
+                }
+                if cigar_op.consume_ref(){
+                    ref_pos += n;
+                }
+                if cigar_op.consume_que(){
+                    que_pos += n;
+                }
+            }
+            mismatches
+        }
+
+        /// given the same arguments as count_mismatches(), return the edit distance to the reference (SAM NM tag):
+        /// mismatching aligned bases plus inserted and deleted bases. skipped (N) and clipped bases are not counted.
+        pub fn edit_distance(&self, aln_start: i64, ref_seq: &[u8], query_seq: &[u8], query_start: i64) -> i64{
+            self.count_mismatches(aln_start, ref_seq, query_seq, query_start) + self.sum_ops('I') + self.sum_ops('D')
+        }
+
+        /// return a copy of the cigar without its hard clips ("5H10S50M" -> "10S50M"), soft clips are kept.
+        /// query coordinates of the result line up with the SEQ stored for a hard clipped record.
+        pub fn drop_hard_clips(&self) -> Cigar{
+            Cigar{ cigar: self.cigar.iter().filter(|op| !matches!(op, CigarOperation::Hard(_))).copied().collect() }
+        }
+
+        /// given the start of the alignment on the reference and on the query, lazily yield the pileup events of the read in order:
+        /// a Match per aligned (M) base, a Del per deleted base, an Ins per insertion (anchored before ref_anchor)
+        /// and a Skip per skipped region. clipped and padded operations yield nothing.
+        pub fn pileup_events(&self, aln_start: i64, query_start: i64) -> impl Iterator<Item = PileupEvent> + '_{
+            let mut ref_pos = aln_start;
+            let mut que_pos = query_start;
+            self.cigar.iter()
+            .flat_map(move |cigar_op| {
+                let (r, q) = (ref_pos, que_pos);
+                let n = cigar_op.length();
+                if cigar_op.consume_ref(){
+                    ref_pos += n;
+                }
+                if cigar_op.consume_que(){
+                    que_pos += n;
+                }
+                let count = match cigar_op{
+                    op if op.is_aligned() => n,
+                    CigarOperation::Deletion(_) => n,
+                    CigarOperation::Insertion(_) | CigarOperation::Nskipped(_) => 1,
+                    _ => 0,
+                };
+                let cigar_op = *cigar_op;
+                (0..count).map(move |i| match cigar_op{
+                    CigarOperation::Deletion(_) => PileupEvent::Del{ ref_pos: r + i },
+                    CigarOperation::Insertion(len) => PileupEvent::Ins{ ref_anchor: r, query_pos: q, len },
+                    CigarOperation::Nskipped(len) => PileupEvent::Skip{ start: r, end: r + len },
+                    _ => PileupEvent::Match{ ref_pos: r + i, query_pos: q + i },
+                })
+            })
+        }
+
+        /// given the start of the alignment, return the exons (reference [start, end) intervals) of the read,
+        /// the complement of the skipped regions: "35M110N45M" at 100 gives [(100, 135), (245, 290)].
+        /// deletions do not split an exon.
+        pub fn splice_skeleton(&self, aln_start: i64) -> Vec<(i64, i64)>{
+            let mut results = Vec::new();
+            let mut exon_start = aln_start;
+            let mut ref_pos = aln_start;
+            for cigar_op in self.cigar.iter(){
+                match cigar_op{
+                    CigarOperation::Nskipped(n) => {
+                        if ref_pos > exon_start{
+                            results.push((exon_start, ref_pos));
+                        }
+                        ref_pos += n;
+                        exon_start = ref_pos;
+                    },
+                    op if op.consume_ref() => { ref_pos += op.length(); },
+                    _ => ()
+                }
+            }
+            if ref_pos > exon_start{
+                results.push((exon_start, ref_pos));
+            }
+            results
+        }
+
+        /// return true if a terminal soft clip (either end) is at least min_clip_bases long
+        /// and at least min_clip_frac of the read length, the usual split read / chimeric screen.
+        pub fn is_chimeric_candidate(&self, min_clip_frac: f64, min_clip_bases: i64) -> bool{
+            let read_len = self.get_read_length_from_cigar();
+            if read_len == 0{
+                return false;
+            }
+            let (five, three) = self.soft_clips();
+            [five, three].iter()
+            .any(|clip| *clip >= min_clip_bases && (*clip as f64 / read_len as f64) >= min_clip_frac)
+        }
+
+        /// return the operations of the cigar as a slice.
+        pub fn operations(&self) -> &[CigarOperation]{
+            &self.cigar
+        }
+
+        /// return the operations of the cigar as a mutable slice, to edit lengths in place.
+        /// the cigar is not checked nor normalized afterward, the caller is responsible for calling normalize() if needed.
+        pub fn operations_mut(&mut self) -> &mut [CigarOperation]{
+            &mut self.cigar
+        }
+
+        /// return the total length of the reference only gaps, deletions plus skipped (D + N).
+        pub fn reference_gap_total(&self) -> i64{
+            self.sum_ops('D') + self.sum_ops('N')
+        }
+
+        /// return the total length of the query only gaps, the inserted bases (I).
+        pub fn query_gap_total(&self) -> i64{
+            self.sum_ops('I')
+        }
+
+        /// return a normalized copy of the cigar where each skipped region (N) flanked by an aligned (M) run shorter than min_anchor
+        /// (on either side) is turned into a deletion, a weakly anchored intron being likely an alignment artifact.
+        /// the anchor is the total length of the consecutive aligned operations directly next to the N.
+        pub fn drop_weak_junctions(&self, min_anchor: i64) -> Cigar{
+            let anchor = |ops: &mut dyn Iterator<Item = &CigarOperation>| -> i64 {
+                ops.take_while(|op| op.is_aligned()).map(|op| op.length()).sum()
+            };
+            let operations: Vec<CigarOperation> = self.cigar.iter().enumerate()
+            .map(|(i, cigar_op)| match cigar_op{
+                CigarOperation::Nskipped(n) => {
+                    let left = anchor(&mut self.cigar[..i].iter().rev());
+                    let right = anchor(&mut self.cigar[i + 1..].iter());
+                    if left < min_anchor || right < min_anchor { CigarOperation::Deletion(*n) } else { *cigar_op }
+                },
+                op => *op
+            })
+            .collect();
+            Cigar{ cigar: operations }.normalize()
+        }
+
+        /// given the start of the alignment, the full reference (indexed by aln_start) and the read SEQ (soft clipped bases included),
+        /// return the minimap2 short cs tag (without the "cs:Z:" prefix): ":N" identical bases, "*ab" substitution of ref a by query b,
+        /// "+seq" insertion, "-seq" deletion and "~gt100ag" intron (two first bases, length, two last bases).
+        /// bases are written lowercase, bases falling outside the given sequences are written 'n'.
+        pub fn to_cs_tag(&self, aln_start: i64, ref_seq: &[u8], query_seq: &[u8]) -> String{
+            let base = |seq: &[u8], i: i64| -> char {
+                usize::try_from(i).ok().and_then(|i| seq.get(i)).map_or('n', |b| b.to_ascii_lowercase() as char)
+            };
+            let mut cs = String::new();
+            let mut ref_pos = aln_start;
+            let mut que_pos = 0;
+
+            for cigar_op in self.cigar.iter(){
+                let n = cigar_op.length();
+                match cigar_op{
+                    op if op.is_aligned() => {
+                        let mut identical = 0;
+                        for i in 0..n{
+                            let (r, q) = (base(ref_seq, ref_pos + i), base(query_seq, que_pos + i));
+                            if r == q{
+                                identical += 1;
+                                continue;
+                            }
+                            if identical > 0{
+                                let _ = write!(cs, ":{}", identical);
+                                identical = 0;
+                            }
+                            let _ = write!(cs, "*{}{}", r, q);
+                        }
+                        if identical > 0{
+                            let _ = write!(cs, ":{}", identical);
+                        }
+                    },
+                    CigarOperation::Insertion(_) => {
+                        cs.push('+');
+                        cs.extend((0..n).map(|i| base(query_seq, que_pos + i)));
+                    },
+                    CigarOperation::Deletion(_) => {
+                        cs.push('-');
+                        cs.extend((0..n).map(|i| base(ref_seq, ref_pos + i)));
+                    },
+                    CigarOperation::Nskipped(_) => {
+                        // the motif never reads outside the intron, an intron shorter than 2 bases gives 'n'
+                        let motif = |i: i64| if i >= ref_pos && i < ref_pos + n { base(ref_seq, i) } else { 'n' };
+                        let _ = write!(cs, "~{}{}{}{}{}", motif(ref_pos), motif(ref_pos + 1), n, motif(ref_pos + n - 2), motif(ref_pos + n - 1));
+                    },
+                    _ => ()
+                }
+                if cigar_op.consume_ref(){
+                    ref_pos += n;
+                }
+                if cigar_op.consume_que(){
+                    que_pos += n;
+                }
+            }
+            cs
+        }
+
+        /// build a Cigar from a minimap2 cs tag (short or long form, the "cs:Z:" prefix is optional), see to_cs_tag().
+        /// identical (":N" or "=seq") and substituted ("*ab") bases are merged into M, "+seq" gives I, "-seq" D and "~gt100ag" N.
+        /// Will return an error if the tag is malformed.
+        pub fn from_cs_tag(cs: &str) -> Result<Cigar, CigarError>{
+            let bytes = cs.strip_prefix("cs:Z:").unwrap_or(cs).as_bytes();
+            let run = |i: usize, pred: fn(&u8) -> bool| -> usize {
+                bytes[i..].iter().take_while(|b| pred(b)).count()
+            };
+            let number = |i: usize, len: usize| -> Result<i64, CigarError> {
+                core::str::from_utf8(&bytes[i..i + len]).ok()
+                .and_then(|digits| digits.parse::<i64>().ok())
+                .ok_or(CigarError::Parse)
+            };
+            let mut operations: Vec<CigarOperation> = Vec::new();
+            let mut i = 0;
+            while i < bytes.len(){
+                let tag = bytes[i];
+                i += 1;
+                let op = match tag{
+                    b':' => {
+                        let len = run(i, u8::is_ascii_digit);
+                        let n = number(i, len)?;
+                        i += len;
+                        CigarOperation::Match(n)
+                    },
+                    b'=' | b'+' | b'-' => {
+                        let len = run(i, u8::is_ascii_alphabetic);
+                        if len == 0{
+                            return Err(CigarError::Parse);
+                        }
+                        i += len;
+                        CigarOperation::from_symbol(match tag { b'=' => 'M', b'+' => 'I', _ => 'D' }, len as i64)
+                    },
+                    b'*' => {
+                        if run(i, u8::is_ascii_alphabetic) < 2{
+                            return Err(CigarError::Parse);
+                        }
+                        i += 2;
+                        CigarOperation::Match(1)
+                    },
+                    b'~' => {
+                        if run(i, u8::is_ascii_alphabetic) < 2{
+                            return Err(CigarError::Parse);
+                        }
+                        i += 2;
+                        let len = run(i, u8::is_ascii_digit);
+                        let n = number(i, len)?;
+                        i += len;
+                        if run(i, u8::is_ascii_alphabetic) < 2{
+                            return Err(CigarError::Parse);
+                        }
+                        i += 2;
+                        CigarOperation::Nskipped(n)
+                    },
+                    _ => return Err(CigarError::Parse),
+                };
+                operations.push(op);
+            }
+            Ok(Cigar{ cigar: operations }.normalize())
+        }
+
+        /// return the run-length encoding (length, covered) of the reference footprint of the alignment,
+        /// aligned (M) bases are covered runs, deletions and skipped are uncovered runs ("10M2D5M100N5M" -> [(10, true), (2, false), (5, true), (100, false), (5, true)]).
+        /// consecutive runs with the same state are merged.
+        /// aln_start is unused (runs are lengths), it is kept so the signature matches the other reference walks.
+        pub fn reference_coverage_rle(&self, _aln_start: i64) -> Vec<(i64, bool)>{
+            let mut results: Vec<(i64, bool)> = Vec::new();
+            for cigar_op in self.cigar.iter().filter(|op| op.consume_ref() && op.length() > 0){
+                let covered = cigar_op.is_aligned();
+                match results.last_mut(){
+                    Some(last) if last.1 == covered => { last.0 += cigar_op.length(); },
+                    _ => results.push((cigar_op.length(), covered)),
+                }
+            }
+            results
+        }
+
+        /// given the start of the alignment and a list of features as reference [start, end) intervals (e.g. the exons of a gene),
+        /// return the number of aligned (M) bases of the read falling in the features, introns and deletions of the read are not counted.
+        /// the features are not merged: a base falling in two overlapping features is counted twice.
+        pub fn overlap_with_intervals(&self, aln_start: i64, features: &[(i64, i64)]) -> i64{
+            self.aligned_ref_blocks(aln_start)
+            .map(|block| features.iter()
+                .map(|(st, en)| (block.end.min(*en) - block.start.max(*st)).max(0))
+                .sum::<i64>())
+            .sum()
+        }
+
+        /// given the start of the alignment and the chromosome name, return one "chrom:donor-acceptor" id per intron.
+        /// coordinates are the ones of get_skipped_pos_on_ref(), in the same system as aln_start (no +1 is applied):
+        /// donor is the first intronic base and acceptor the first exonic base after the intron, the intron is [donor, acceptor).
+        /// "35M110N45M" at 100 on "chr1" gives ["chr1:135-245"].
+        pub fn junction_ids(&self, aln_start: i64, chrom: &str) -> Vec<String>{
+            match self.get_skipped_pos_on_ref(&aln_start){
+                Some(junctions) => junctions.chunks(2)
+                    .map(|j| format!("{}:{}-{}", chrom, j[0], j[1]))
+                    .collect(),
+                None => Vec::new(),
+            }
+        }
+
+        /// given the start of the alignment, split the cigar at each deletion longer than max_del and return each piece with its reference start.
+        /// the query bases outside of a piece are soft clipped so every piece still describes the whole read, hard clips are kept on every piece.
+        /// "10M50D10M" at 100 with max_del 20 gives [(100, "10M10S"), (160, "10S10M")].
+        pub fn split_on_deletion(&self, aln_start: i64, max_del: i64) -> Vec<(i64, Cigar)>{
+            let range = self.unclipped_range();
+            let clip_sum = |ops: &[CigarOperation], symbol: char| -> i64 {
+                ops.iter().filter(|op| op.symbol() == symbol).map(|op| op.length()).sum()
+            };
+            let (lead, trail) = (&self.cigar[..range.start], &self.cigar[range.end..]);
+
+            let mut groups: Vec<(i64, Vec<CigarOperation>)> = vec![(aln_start, Vec::new())];
+            let mut ref_pos = aln_start;
+            for cigar_op in self.cigar[range].iter(){
+                if cigar_op.consume_ref(){
+                    ref_pos += cigar_op.length();
+                }
+                match cigar_op{
+                    CigarOperation::Deletion(n) if *n > max_del => groups.push((ref_pos, Vec::new())),
+                    op => groups.last_mut().unwrap().1.push(*op),
+                }
+            }
+
+            let query_len = |ops: &[CigarOperation]| -> i64 { ops.iter().filter(|op| op.consume_que()).map(|op| op.length()).sum() };
+            let mut before = clip_sum(lead, 'S');
+            let mut after = groups.iter().map(|group| query_len(&group.1)).sum::<i64>() + clip_sum(trail, 'S');
+            let mut results = Vec::with_capacity(groups.len());
+            for (start, ops) in groups{
+                let consumed = query_len(&ops);
+                after -= consumed;
+                // insertions at the edges of a piece flank the split, they join the soft clips
+                let is_ins = |op: &&CigarOperation| matches!(op, CigarOperation::Insertion(_));
+                let lead_ins = ops.iter().take_while(is_ins).count();
+                let trail_ins = ops[lead_ins..].iter().rev().take_while(is_ins).count();
+                let core = &ops[lead_ins..ops.len() - trail_ins];
+                if !core.is_empty(){
+                    let mut operations = Vec::with_capacity(core.len() + 4);
+                    let edges = [
+                        CigarOperation::Hard(clip_sum(lead, 'H')),
+                        CigarOperation::Soft(before + query_len(&ops[..lead_ins])),
+                    ];
+                    operations.extend(edges.iter().filter(|op| op.length() > 0));
+                    operations.extend_from_slice(core);
+                    let edges = [
+                        CigarOperation::Soft(after + query_len(&ops[ops.len() - trail_ins..])),
+                        CigarOperation::Hard(clip_sum(trail, 'H')),
+                    ];
+                    operations.extend(edges.iter().filter(|op| op.length() > 0));
+                    results.push((start, Cigar{ cigar: operations }));
+                }
+                before += consumed;
+            }
+            results
+        }
+
+        /// return the number of aligned blocks of the read (an exon count proxy): runs of aligned (M) operations separated by a skipped region (N).
+        /// insertions and deletions do not break a block, "10M2D5M100N5M1I5M" has 2 blocks. a cigar without aligned operation has 0 block.
+        pub fn aligned_block_count(&self) -> usize{
+            let mut count = 0;
+            let mut in_block = false;
+            for cigar_op in self.cigar.iter(){
+                match cigar_op{
+                    op if op.is_aligned() && !in_block => {
+                        count += 1;
+                        in_block = true;
+                    },
+                    CigarOperation::Nskipped(_) => { in_block = false; },
+                    _ => ()
+                }
+            }
+            count
+        }
+
+        /// return a copy of the cigar with n more soft clipped bases at the start, merged with the existing soft clip if any
+        /// and placed after the hard clip ("2H5S10M" + 3 -> "2H8S10M"). usefull to get back the original read frame after an upstream trimming.
+        pub fn prepend_soft_clip(&self, n: i64) -> Cigar{
+            let mut operations = self.cigar.clone();
+            if n > 0{
+                let i = operations.iter().take_while(|op| matches!(op, CigarOperation::Hard(_))).count();
+                match operations.get_mut(i){
+                    Some(CigarOperation::Soft(m)) => { *m += n; },
+                    _ => operations.insert(i, CigarOperation::Soft(n)),
+                }
+            }
+            Cigar{ cigar: operations }
+        }
+
+        /// return a copy of the cigar with n more soft clipped bases at the end, merged with the existing soft clip if any
+        /// and placed before the hard clip ("10M5S2H" + 3 -> "10M8S2H").
+        pub fn append_soft_clip(&self, n: i64) -> Cigar{
+            let mut operations = self.cigar.clone();
+            if n > 0{
+                let i = operations.len() - operations.iter().rev().take_while(|op| matches!(op, CigarOperation::Hard(_))).count();
+                match i.checked_sub(1).and_then(|last| operations.get_mut(last)){
+                    Some(CigarOperation::Soft(m)) => { *m += n; },
+                    _ => operations.insert(i, CigarOperation::Soft(n)),
+                }
+            }
+            Cigar{ cigar: operations }
+        }
+
+        /// return the spliced (exonic) reference length of the alignment: aligned (M) and deleted bases, skipped (N) are not counted.
+        /// the genomic span is get_end_of_aln(aln_start) - aln_start, which does count the introns.
+        pub fn exonic_reference_length(&self) -> i64{
+            self.cigar.iter()
+            .filter(|op| op.consume_ref() && !matches!(op, CigarOperation::Nskipped(_)))
+            .map(|op| op.length())
+            .sum()
+        }
+
+        /// given the start of the alignment, keep the part of the alignment before ref_stop and soft clip the rest of the query,
+        /// e.g. for an alignment crossing a contig boundary. this is reference_prefix(aln_start, ref_stop), see it for the details.
+        pub fn truncate_at_reference(&self, aln_start: i64, ref_stop: i64) -> Cigar{
+            self.reference_prefix(aln_start, ref_stop)
+        }
+
+        /// return the number of reference bases consumed by the operation at index, 0 if it does not consume the reference,
+        /// None if index is out of range.
+        pub fn ref_span_of(&self, index: usize) -> Option<i64>{
+            self.cigar.get(index).map(|op| if op.consume_ref() { op.length() } else { 0 })
+        }
+
+        /// return the number of query bases consumed by the operation at index, 0 if it does not consume the query,
+        /// None if index is out of range.
+        pub fn query_span_of(&self, index: usize) -> Option<i64>{
+            self.cigar.get(index).map(|op| if op.consume_que() { op.length() } else { 0 })
+        }
+
+        /// return, for each operation, the reference offset (from the alignment start) before it: "5S10M2D5M" -> [0, 0, 10, 12].
+        /// the table is sorted, a binary search on it (aln_start + offset) finds the operation covering a reference position in O(log n),
+        /// callers doing many lookups on the same cigar should build it once.
+        pub fn reference_offset_table(&self) -> Vec<i64>{
+            let mut offset = 0;
+            self.cigar.iter()
+            .map(|op| {
+                let before = offset;
+                if op.consume_ref(){
+                    offset += op.length();
+                }
+                before
+            })
+            .collect()
+        }
+
+        /// return, for each operation, the query offset (from the first base of the cigar) before it: "5S10M2D5M" -> [0, 5, 15, 15].
+        /// companion of reference_offset_table(), see offset_tables() to build both.
+        pub fn query_offset_table(&self) -> Vec<i64>{
+            let mut offset = 0;
+            self.cigar.iter()
+            .map(|op| {
+                let before = offset;
+                if op.consume_que(){
+                    offset += op.length();
+                }
+                before
+            })
+            .collect()
+        }
+
+        /// return both the reference and the query offset tables, to be built once and passed to query_to_ref_indexed().
+        pub fn offset_tables(&self) -> OffsetTables{
+            OffsetTables{ reference: self.reference_offset_table(), query: self.query_offset_table() }
+        }
+
+        /// given the offset tables of this cigar and the start of the alignment on the reference and on the query,
+        /// return the reference position aligned with query_pos in O(log n),
+        /// None if query_pos is soft clipped, inserted or outside the read (or if tables were not built from this cigar).
+        pub fn query_to_ref_indexed(&self, tables: &OffsetTables, aln_start: i64, query_start: i64, query_pos: i64) -> Option<i64>{
+            let offset = query_pos - query_start;
+            let i = tables.query.partition_point(|before| *before <= offset).checked_sub(1)?;
+            let cigar_op = self.cigar.get(i)?;
+            let que_before = tables.query.get(i)?;
+            if !cigar_op.is_aligned() || offset >= que_before + cigar_op.length(){
+                return None;
+            }
+            Some(aln_start + tables.reference.get(i)? + offset - que_before)
+        }
+
+        /// return a normalized copy of the cigar where clusters of insertions and deletions separated by aligned runs shorter than max_separation
+        /// are collapsed into a single net event: the net insertion (or deletion) placed at the first indel of the cluster, followed by the aligned bases.
+        /// "10M2I3M3D10M" with max_separation 5 gives "10M1D15M". the reference and query lengths are preserved, skipped (N) and clips break a cluster.
+        pub fn collapse_complex_indels(&self, max_separation: i64) -> Cigar{
+            let is_indel = |op: &CigarOperation| matches!(op, CigarOperation::Insertion(_) | CigarOperation::Deletion(_));
+            let ops = self.normalize().cigar;
+            let mut operations: Vec<CigarOperation> = Vec::with_capacity(ops.len());
+            let mut i = 0;
+            while i < ops.len(){
+                if !is_indel(&ops[i]){
+                    operations.push(ops[i]);
+                    i += 1;
+                    continue;
+                }
+                // extend the cluster while the next indel is close enough
+                let mut end = i;
+                loop{
+                    let run = ops[end + 1..].iter().take_while(|op| op.is_aligned());
+                    let (count, run_len) = run.fold((0, 0), |acc, op| (acc.0 + 1, acc.1 + op.length()));
+                    match ops.get(end + 1 + count){
+                        Some(op) if is_indel(op) && run_len < max_separation => { end += 1 + count; },
+                        _ => break,
+                    }
+                }
+                let cluster = &ops[i..=end];
+                let sum = |symbol: char| -> i64 { cluster.iter().filter(|op| op.symbol() == symbol).map(|op| op.length()).sum() };
+                let (inserted, deleted) = (sum('I'), sum('D'));
+                let aligned: i64 = cluster.iter().filter(|op| op.is_aligned()).map(|op| op.length()).sum();
+                if inserted > deleted{
+                    operations.push(CigarOperation::Insertion(inserted - deleted));
+                }
+                else if deleted > inserted{
+                    operations.push(CigarOperation::Deletion(deleted - inserted));
+                }
+                operations.push(CigarOperation::Match(aligned + inserted.min(deleted)));
+                i = end + 1;
+            }
+            Cigar{ cigar: operations }.normalize()
+        }
+
+        /// parse a cigar string like from_str() and normalize it: zero length operations are dropped
+        /// and adjacent operations of the same type are merged ("0M150M" -> "150M"). from_str() keeps the operations as written.
+        pub fn from_str_normalized(s: &str) -> Result<Cigar, CigarError>{
+            Ok(s.parse::<Cigar>()?.normalize())
+        }
+
+        /// given the start of the alignment, return the midpoint of the full reference span, (aln_start + get_end_of_aln()) / 2.
+        /// introns and deletions are part of the span, the midpoint may fall in an intron.
+        pub fn reference_midpoint(&self, aln_start: i64) -> i64{
+            (aln_start + self.get_end_of_aln(&aln_start)) / 2
+        }
+
+        /// given the start of the alignment, return in order each operation overlapping the reference window [win_start, win_end)
+        /// with its full (not clamped) reference range. an insertion has an empty range at its anchor and is returned
+        /// when both flanking reference bases are in the window. clips are never returned.
+        pub fn operations_in_reference_window(&self, aln_start: i64, win_start: i64, win_end: i64) -> Vec<(CigarOperation, Range<i64>)>{
+            let mut ref_pos = aln_start;
+            let mut results = Vec::new();
+            for cigar_op in self.cigar.iter(){
+                let range = if cigar_op.consume_ref(){
+                    ref_pos += cigar_op.length();
+                    ref_pos - cigar_op.length()..ref_pos
+                } else { ref_pos..ref_pos };
+                let overlaps = match cigar_op{
+                    CigarOperation::Insertion(_) => range.start > win_start && range.start < win_end,
+                    op if op.consume_ref() => range.start < win_end && range.end > win_start,
+                    _ => false,
+                };
+                if overlaps{
+                    results.push((*cigar_op, range));
+                }
+            }
+            results
+        }
+
+        /// best effort cleanup of a malformed cigar, return the repaired cigar and the list of changes made:
+        /// zero length operations are dropped, terminal deletions are removed, terminal insertions become soft clips,
+        /// hard clips are moved outside the soft clips and adjacent operations of the same type are merged.
+        /// a removed leading deletion shifts the alignment start, its length is given by the StrippedDeletion action.
+        pub fn repair(&self) -> (Cigar, Vec<RepairAction>){
+            let mut actions = Vec::new();
+            let mut cig = Cigar{ cigar: self.cigar.iter().filter(|op| op.length() > 0).copied().collect() };
+            let dropped = self.cigar.len() - cig.cigar.len();
+            if dropped > 0{
+                actions.push(RepairAction::DroppedZeroLength(dropped));
+            }
+
+            // removing or clipping a terminal indel can expose another one
+            loop{
+                let range = cig.unclipped_range();
+                if range.is_empty(){
+                    break;
+                }
+                let mut changed = false;
+                for (i, end) in [(range.start, End::Five), (range.end - 1, End::Three)]{
+                    match cig.cigar[i]{
+                        CigarOperation::Deletion(length) => {
+                            cig.cigar.remove(i);
+                            actions.push(RepairAction::StrippedDeletion{ end, length });
+                        },
+                        CigarOperation::Insertion(length) => {
+                            cig.cigar[i] = CigarOperation::Soft(length);
+                            actions.push(RepairAction::InsertionToSoftClip{ end, length });
+                        },
+                        _ => continue,
+                    }
+                    changed = true;
+                    break;
+                }
+                if !changed{
+                    break;
+                }
+            }
+
+            let range = cig.unclipped_range();
+            let rebuild_clips = |clips: &[CigarOperation], hard_first: bool| -> Vec<CigarOperation> {
+                let sum = |symbol: char| -> i64 { clips.iter().filter(|op| op.symbol() == symbol).map(|op| op.length()).sum() };
+                let (hard, soft) = (CigarOperation::Hard(sum('H')), CigarOperation::Soft(sum('S')));
+                let ordered = if hard_first { [hard, soft] } else { [soft, hard] };
+                ordered.iter().filter(|op| op.length() > 0).copied().collect()
+            };
+            let misplaced = |clips: &[CigarOperation], first: char, second: char| -> bool {
+                clips.windows(2).any(|pair| pair[0].symbol() == first && pair[1].symbol() == second)
+            };
+            let (lead, core, trail) = (&cig.cigar[..range.start], &cig.cigar[range.clone()], &cig.cigar[range.end..]);
+            let mut operations = Vec::with_capacity(cig.cigar.len());
+            if misplaced(lead, 'S', 'H'){
+                actions.push(RepairAction::MovedHardClip(End::Five));
+                operations.extend(rebuild_clips(lead, true));
+            }
+            else{
+                operations.extend_from_slice(lead);
+            }
+            operations.extend_from_slice(core);
+            if misplaced(trail, 'H', 'S'){
+                actions.push(RepairAction::MovedHardClip(End::Three));
+                operations.extend(rebuild_clips(trail, false));
+            }
+            else{
+                operations.extend_from_slice(trail);
+            }
+
+            let rebuilt_len = operations.len();
+            let repaired = Cigar{ cigar: operations }.normalize();
+            if repaired.cigar.len() < rebuilt_len{
+                actions.push(RepairAction::MergedAdjacent);
+            }
+            (repaired, actions)
+        }
+
+        /// given the start of the alignment and a payload (e.g. the read id), return (block_start, block_end, payload) for each aligned (M) block,
+        /// ready to be inserted in an interval index.
+        pub fn block_entries<T: Clone>(&self, aln_start: i64, payload: T) -> Vec<(i64, i64, T)>{
+            self.aligned_ref_blocks(aln_start)
+            .map(|block| (block.start, block.end, payload.clone()))
+            .collect()
+        }
+
+        /// return true if the cigar has a hard clip (H), supplementary alignments usually hard clip while primary ones soft clip.
+        pub fn uses_hard_clips(&self) -> bool{
+            self.cigar.iter().any(|op| matches!(op, CigarOperation::Hard(_)))
+        }
+
+        /// given the start of the alignment on the reference and on the query, lazily yield (ref_range, query_range) for each aligned (M) block,
+        /// gaps and clips are skipped.
+        pub fn iter_match_blocks(&self, aln_start: i64, query_start: i64) -> impl Iterator<Item = (Range<i64>, Range<i64>)> + '_{
+            let mut ref_pos = aln_start;
+            let mut que_pos = query_start;
+            self.cigar.iter()
+            .filter_map(move |cigar_op| {
+                let (r, q) = (ref_pos, que_pos);
+                if cigar_op.consume_ref(){
+                    ref_pos += cigar_op.length();
+                }
+                if cigar_op.consume_que(){
+                    que_pos += cigar_op.length();
+                }
+                if cigar_op.is_aligned(){
+                    Some((r..ref_pos, q..que_pos))
+                }
+                else{
+                    None
+                }
+            })
+        }
+
+        /// return the total number of clipped bases, soft and hard, at both ends.
+        pub fn total_clipped_bases(&self) -> i64{
+            self.sum_ops('S') + self.sum_ops('H')
+        }
+
+        /// flip a query coordinate between the two orientations of the read: get_read_length_from_cigar() - query_pos.
+        /// this maps interval boundaries ([st, en) becomes [flip(en), flip(st))), the index of a single base is flip(query_pos) - 1.
+        pub fn flip_query_coordinate(&self, query_pos: i64) -> i64{
+            self.get_read_length_from_cigar() - query_pos
+        }
+
+        /// return true if the cigar has at least one operation of the given symbol ('M', 'I', 'N', 'H'...).
+        pub fn contains_op(&self, symbol: char) -> bool{
+            self.cigar.iter().any(|op| op.symbol() == symbol)
+        }
+
+        /// given the start of the alignment on the reference and on the query, return the query position aligned with ref_pos,
+        /// or why there is none: the position is deleted (D), skipped (N) or outside the alignment.
+        pub fn ref_to_query_detailed(&self, aln_start: i64, query_start: i64, ref_pos: i64) -> RefMapResult{
+            let mut ref_cursor = aln_start;
+            let mut que_cursor = query_start;
+            for cigar_op in self.cigar.iter(){
+                let n = cigar_op.length();
+                if cigar_op.consume_ref() && ref_pos >= ref_cursor && ref_pos < ref_cursor + n{
+                    return match cigar_op{
+                        CigarOperation::Deletion(_) => RefMapResult::Deleted,
+                        CigarOperation::Nskipped(_) => RefMapResult::Skipped,
+                        _ => RefMapResult::Aligned(que_cursor + ref_pos - ref_cursor),
+                    };
+                }
+                if cigar_op.consume_ref(){
+                    ref_cursor += n;
+                }
+                if cigar_op.consume_que(){
+                    que_cursor += n;
+                }
+            }
+            RefMapResult::OutOfRange
+        }
+
+        /// given the same arguments as count_mismatches(), return the cigar with each M operation replaced by runs of
+        /// sequence matches (=) and mismatches (X), the other operations are left unchanged ("5M1I5M" -> "2=1X2=1I5=").
+        /// mismatches follow count_mismatches(): case insensitive, bases falling outside the given sequences are not mismatches (=).
+        /// the result is normalized, adjacent runs of the same type are merged ("5M5=" -> "10=").
+        pub fn to_eqx(&self, aln_start: i64, ref_seq: &[u8], query_seq: &[u8], query_start: i64) -> Cigar{
+            let mut operations: Vec<CigarOperation> = Vec::with_capacity(self.cigar.len());
+            let mut ref_pos = aln_start;
+            let mut que_pos = query_start;
+            for cigar_op in self.cigar.iter(){
+                let n = cigar_op.length();
+                if let CigarOperation::Match(_) = cigar_op{
+                    for i in 0..n{
+                        let same = !Cigar::bases_differ(ref_seq, query_seq, ref_pos + i, que_pos + i);
+                        match operations.last_mut(){
+                            Some(CigarOperation::SeqMatch(m)) if same => { *m += 1; },
+                            Some(CigarOperation::SeqMismatch(m)) if !same => { *m += 1; },
+                            _ => operations.push(if same { CigarOperation::SeqMatch(1) } else { CigarOperation::SeqMismatch(1) }),
+                        }
+                    }
+                }
+                else{
+                    operations.push(*cigar_op);
+                }
+                if cigar_op.consume_ref(){
+                    ref_pos += n;
+                }
+                if cigar_op.consume_que(){
+                    que_pos += n;
+                }
+            }
+            Cigar{ cigar: operations }.normalize()
+        }
+
+        /// return the number of bytes allocated on the heap for the operations (capacity * size_of::<CigarOperation>()),
+        /// the Cigar struct itself is not counted.
+        pub fn heap_size(&self) -> usize{
+            self.cigar.capacity() * core::mem::size_of::<CigarOperation>()
+        }
+
+        /// shrink the capacity of the operations to their number, releasing the extra memory.
+        /// when a Cigar is kept around after edits or in a long running loop, call it once in a while after unusually long cigars.
+        pub fn shrink_to_fit(&mut self){
+            self.cigar.shrink_to_fit();
+        }
+
+        /// given the start of the alignment and a set of known junctions (donor, acceptor) in the get_skipped_pos_on_ref() coordinates,
+        /// return each junction of the read with true if it is known, false if it is novel.
+        #[cfg(feature = "std")]
+        pub fn classify_junctions(&self, aln_start: i64, known: &std::collections::HashSet<(i64, i64)>) -> Vec<(i64, i64, bool)>{
+            match self.get_skipped_pos_on_ref(&aln_start){
+                Some(junctions) => junctions.chunks(2)
+                    .map(|j| (j[0], j[1], known.contains(&(j[0], j[1]))))
+                    .collect(),
+                None => Vec::new(),
+            }
+        }
+
+        /// set the length of the operation at index, the safe alternative to operations_mut().
+        /// a zero length removes the operation and its neighbours are merged if they are of the same type ("10M0I5M" -> "15M"),
+        /// the rest of the cigar is left as is.
+        /// Will return an error if new_len is negative or index is out of range, the cigar is then unchanged.
+        pub fn set_operation_length(&mut self, index: usize, new_len: i64) -> Result<(), CigarError>{
+            if new_len < 0{
+                return Err(CigarError::NegativeLength(new_len));
+            }
+            let cigar_op = self.cigar.get_mut(index).ok_or(CigarError::IndexOutOfRange(index))?;
+            if new_len > 0{
+                *cigar_op = cigar_op.with_length(new_len);
+                return Ok(());
+            }
+            self.cigar.remove(index);
+            if index > 0 && index < self.cigar.len() && self.cigar[index - 1].symbol() == self.cigar[index].symbol(){
+                let merged = self.cigar.remove(index);
+                self.cigar[index - 1] = merged.with_length(self.cigar[index - 1].length() + merged.length());
+            }
+            Ok(())
+        }
+
+        /// given the start of the alignment, return a bitset of the region [region_start, region_start + region_len) as u64 words:
+        /// bit (i % 64) of word i / 64 is set if the position region_start + i is covered by an aligned (M) base.
+        /// bitsets of several reads over the same region can then be combined with & and |.
+        pub fn coverage_bitset(&self, aln_start: i64, region_start: i64, region_len: usize) -> Vec<u64>{
+            let mut words = vec![0u64; region_len.div_ceil(64)];
+            let region_end = region_start + region_len as i64;
+            for pos in self.aligned_positions_in_window(aln_start, region_start, region_end){
+                let i = (pos - region_start) as usize;
+                words[i / 64] |= 1 << (i % 64);
+            }
+            words
+        }
+
+        /// given the start of the alignment, return the aligned (M) blocks as reference ranges, merging blocks separated
+        /// by a gap (D, N) of at most max_gap bases. this is segments(aln_start, max_gap), named for coverage track building.
+        pub fn merged_blocks(&self, aln_start: i64, max_gap: i64) -> Vec<Range<i64>>{
+            self.segments(aln_start, max_gap)
+        }
+        
+    }
+
+    /// Concatenate two cigars, see Cigar::concat(): the operations at the junction are merged when they have
+    /// the same type, "10M" + "5M2I" gives "15M2I" and not "10M5M2I".
+    impl Add for Cigar {
+        type Output = Cigar;
+
+        fn add(self, other: Cigar) -> Cigar {
+            self.concat(&other)
+        }
+    }
+
+    impl AddAssign for Cigar {
+        fn add_assign(&mut self, other: Cigar) {
+            *self = self.concat(&other);
+        }
+    }
+
+    /// Sum of cigars by concatenation, an empty iterator gives the empty cigar.
+    impl Sum for Cigar {
+        fn sum<I: Iterator<Item = Cigar>>(iter: I) -> Cigar {
+            iter.fold(Cigar::default(), |acc, cigar| acc.concat(&cigar))
+        }
+    }
+
+    impl fmt::Display for Cigar {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            // written operation by operation so formatting does not allocate
+            for op in &self.cigar{
+                match op{
+                    CigarOperation::Unaligned => write!(f, "*")?,
+                    CigarOperation::Invalid => panic!("Invalid CIGAR operation"),
+                    _ => write!(f, "{}{}", op.length(), op.symbol())?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+
+    #[cfg(test)]
+    mod tests {
+        use crate::cigar::{Cigar, CigarOperation};
+        use super::*;
+        use alloc::vec;
+        use alloc::string::ToString;
+        #[test]
+        fn test_from() {
+            let cig = Cigar::from("35M110N45M3I45M10N");
+            assert_eq!(cig, Cigar{ cigar: vec![CigarOperation::Match(35), CigarOperation::Nskipped(110), CigarOperation::Match(45), CigarOperation::Insertion(3),
+            CigarOperation::Match(45), CigarOperation::Nskipped(10)]});
+        }
+        #[test]
+        #[should_panic]
+        fn test_from_panic() {
+            let cig = Cigar::from("35M110N45M3I45M10N50K");
+        }
+        #[test]
+        fn test_from_str(){
+            let cig = Cigar::from_str("35M110N45M3I45M10N");
+            assert_eq!(cig.unwrap(), Cigar{ cigar: vec![CigarOperation::Match(35), CigarOperation::Nskipped(110), CigarOperation::Match(45), CigarOperation::Insertion(3),
+                CigarOperation::Match(45), CigarOperation::Nskipped(10)]});
+        }
+        #[test]
+        fn test_pos(){
+            let cig = Cigar::from("35M110N45M3I45M10N");
+            let results = cig.get_skipped_pos_on_ref(&500);
+            assert_eq!(results, Some(vec![535, 645, 735, 745]))
+        }
+        #[test]
+        fn test_pos_none(){
+            let cig = Cigar::from("35M45M3I45M");
+            let results = cig.get_skipped_pos_on_ref(&500);
+            assert_eq!(results, None)
+        }   
+        #[test]
+        fn test_pos_2(){
+            let cig = Cigar::from("2S80M53373N169M");
+            let results = cig.get_skipped_pos_on_ref(&16946);
+            //assert_eq!(results, None)
+        }   
+        #[test]
+        fn test_match_1(){
+            let cig = Cigar::from("2S80M53373N169M");
+            let results = cig.does_it_match_an_intervall(&500, 550, 560);
+            //println!("{:?}", results);
+            assert_eq!(results, true)
+        }   
+        #[test]
+        fn test_match_outofbound(){
+            let cig = Cigar::from("2S80M53373N169M");
+            let results = cig.does_it_match_an_intervall(&500, 550, 580);
+            assert_eq!(results, false)
+            //assert_eq!(results, None)
+        } 
+        #[test]  
+        fn test_match_s_eq_e(){
+            let cig = Cigar::from("2S80M53373N169M");
+            let results = cig.does_it_match_an_intervall(&500, 550, 550);
+            assert_eq!(results, true)
+            //assert_eq!(results, None)
+        }   
+        #[test]  
+        #[cfg(feature = "std")]
+        fn soft(){
+            let cig = Cigar::from("100M45S");
+            let results = cig.soft_clipped_end(&Strand::Plus, 0);
+            assert_eq!(results, true)
+            //assert_eq!(results, None)
+        }   
+        #[test]  
+        fn test_match_justinbound(){
+            let cig = Cigar::from("2S80M53373N169M45S");
+            let results = cig.does_it_match_an_intervall(&500, 575, 579);
+            assert_eq!(results, true)
+            //assert_eq!(results, None)
+        }   
+        #[test]  
+        #[cfg(feature = "std")]
+        fn softR(){
+            let cig = Cigar::from("2S80M53373N169M45S");
+            let results = cig.soft_clipped_end(&Strand::Minus, 0);
+            assert_eq!(results, true)
+            //assert_eq!(results, None)
+        }   
+        #[test]
+        fn test_breakdown(){
+            let cig = Cigar::from("2S35M110N3I");
+            let results = cig.breakdown(500, 0);
+            assert_eq!(results, vec![
+                OpBreakdown{ symbol: 'S', length: 2, ref_range: None, query_range: Some(0..2) },
+                OpBreakdown{ symbol: 'M', length: 35, ref_range: Some(500..535), query_range: Some(2..37) },
+                OpBreakdown{ symbol: 'N', length: 110, ref_range: Some(535..645), query_range: None },
+                OpBreakdown{ symbol: 'I', length: 3, ref_range: None, query_range: Some(37..40) },
+            ]);
+        }
+        #[test]
+        fn test_query_range_for_reference(){
+            let cig = Cigar::from("5S20M2I10M30N10M");
+            // window fully inside the first block
+            assert_eq!(cig.query_range_for_reference(100, 105, 110), Some((10, 15)));
+            // window over the insertion
+            assert_eq!(cig.query_range_for_reference(100, 115, 125), Some((20, 32)));
+            // window inside the intron
+            assert_eq!(cig.query_range_for_reference(100, 135, 160), None);
+            // window from the intron to the end
+            assert_eq!(cig.query_range_for_reference(100, 135, 200), Some((37, 47)));
+        }
+        #[test]
+        fn test_count_ops(){
+            let cig = Cigar::from("10M2I5M3I");
+            assert_eq!(cig.count_ops('I'), 2);
+            assert_eq!(cig.count_ops('M'), 2);
+            assert_eq!(cig.count_ops('N'), 0);
+        }
+        #[test]
+        fn test_parse_sam_cigar_field(){
+            let line = "read1\t0\tchr1\t500\t60\t35M110N45M\t*\t0\t0\tACGT\tIIII";
+            assert_eq!(parse_sam_cigar_field(line).unwrap(), Cigar::from("35M110N45M"));
+            let line = "read1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tIIII";
+            assert_eq!(parse_sam_cigar_field(line).unwrap(), Cigar{ cigar: vec![] });
+            assert_eq!(parse_sam_cigar_field("read1\t0\tchr1\t500\t60\t35K"), Err(CigarError::Parse));
+            assert_eq!(parse_sam_cigar_field("read1\t0\tchr1"), Err(CigarError::MissingField));
+        }
+        #[test]
+        fn test_reference_footprint(){
+            let reference = b"AAAACCCCGGGGTTTT";
+            let cig = Cigar::from("2S2M2D2M");
+            assert_eq!(cig.reference_footprint(4, reference), b"CCCCGG");
+            // clamped at the end of the reference
+            assert_eq!(cig.reference_footprint(12, reference), b"TTTT");
+            assert_eq!(cig.reference_footprint(20, reference), b"");
+        }
+        #[test]
+        fn test_to_alignment_strings(){
+            let reference = b"AAAACCCCGGGGTTTT";
+            let cig = Cigar::from("1S3M2I2M2D2M");
+            let (r, q) = cig.to_alignment_strings(2, reference, b"TAACTTCCGG");
+            assert_eq!(r, "AAC--CCCGGG");
+            assert_eq!(q, "AACTTCC--GG");
+        }
+        #[test]
+        fn test_op_class(){
+            assert_eq!(CigarOperation::Match(3).class(), OpClass{ consumes_ref: true, consumes_query: true });
+            assert_eq!(CigarOperation::Nskipped(3).class(), OpClass{ consumes_ref: true, consumes_query: false });
+            assert_eq!(CigarOperation::Soft(3).class(), OpClass{ consumes_ref: false, consumes_query: true });
+            assert_eq!(CigarOperation::Hard(3).class(), OpClass{ consumes_ref: false, consumes_query: false });
+            let cig = Cigar::from("2S35M2D10M110N3I45M");
+            assert_eq!(cig.get_end_of_aln(&500), 702);
+            assert_eq!(cig.get_reference_cover(500), vec![500, 535, 537, 547, 657, 702]);
+        }
+        #[test]
+        fn test_normalize(){
+            let cig = Cigar::from("0M10M5M0I2D3D110N20M");
+            assert_eq!(cig.normalize(), Cigar::from("15M5D110N20M"));
+        }
+        #[test]
+        fn test_equivalent(){
+            assert!(Cigar::from("10M5M").equivalent(&Cigar::from("15M")));
+            assert_ne!(Cigar::from("10M5M"), Cigar::from("15M"));
+            assert!(!Cigar::from("10M5D").equivalent(&Cigar::from("15M")));
+        }
+        #[test]
+        fn test_pad_query_to(){
+            let cig = Cigar::from("2S35M110N10M3I");
+            assert_eq!(cig.get_read_length_from_cigar(), 50);
+            assert_eq!(cig.pad_query_to(60).unwrap(), Cigar::from("2S35M110N10M3I10S"));
+            assert_eq!(cig.pad_query_to(50).unwrap(), cig);
+            assert_eq!(cig.pad_query_to(40), Err(CigarError::QueryTooLong));
+            assert_eq!(Cigar::from("10M5S").pad_query_to(20).unwrap(), Cigar::from("10M10S"));
+            assert_eq!(Cigar::from("10M5H").pad_query_to(20).unwrap(), Cigar::from("10M10S5H"));
+        }
+        #[test]
+        fn test_large_insertions(){
+            let cig = Cigar::from("10M1I10M50I5M2D5M20I");
+            assert_eq!(cig.large_insertions(100, 20), vec![(120, 50), (132, 20)]);
+            assert_eq!(cig.large_insertions(100, 1).len(), 3);
+            assert!(cig.large_insertions(100, 51).is_empty());
+        }
+        #[test]
+        fn test_from_expanded(){
+            assert_eq!(Cigar::from_expanded("SMMMIIMMDNNM").unwrap(), Cigar::from("1S3M2I2M1D2N1M"));
+            assert_eq!(Cigar::from_expanded("").unwrap(), Cigar{ cigar: vec![] });
+            assert_eq!(Cigar::from_expanded("MMKM"), Err(CigarError::Parse));
+        }
+        #[test]
+        fn test_longest_match(){
+            let cig = Cigar::from("60S35M110N45M3I12M");
+            assert_eq!(cig.longest_match_ref(), 45);
+            assert_eq!(cig.longest_match_query(), 45);
+            assert_eq!(Cigar::from("*").longest_match_ref(), 0);
+        }
+        #[test]
+        fn test_intron_motif_coordinates(){
+            let cig = Cigar::from("5M10N5M");
+            assert_eq!(cig.intron_motif_coordinates(0), vec![(5, 6, 13, 14)]);
+            let reference = b"AAAAAGTCCCCCCAGTTTT";
+            let (d1, d2, a1, a2) = cig.intron_motif_coordinates(0)[0];
+            let motif = [reference[d1 as usize], reference[d2 as usize], reference[a1 as usize], reference[a2 as usize]];
+            assert_eq!(&motif, b"GTAG");
+            assert!(Cigar::from("35M").intron_motif_coordinates(0).is_empty());
+        }
+        #[test]
+        fn test_into_operations(){
+            let mut ops = Cigar::from("35M110N45M").into_operations();
+            assert_eq!(ops, vec![CigarOperation::Match(35), CigarOperation::Nskipped(110), CigarOperation::Match(45)]);
+            ops.insert(0, CigarOperation::Soft(5));
+            assert_eq!(Cigar::from(ops), Cigar::from("5S35M110N45M"));
+        }
+        #[test]
+        fn test_truncate_query(){
+            let cig = Cigar::from("5S20M2D10M110N15M3H");
+            // inside a block
+            assert_eq!(cig.truncate_query(15), Cigar::from("5S10M35S3H"));
+            // exactly at a block boundary, the trailing deletion is dropped
+            assert_eq!(cig.truncate_query(25), Cigar::from("5S20M25S3H"));
+            // larger than the read
+            assert_eq!(cig.truncate_query(100), cig);
+            assert_eq!(Cigar::from("10M5I10M").truncate_query(12), Cigar::from("10M2I13S"));
+            assert_eq!(Cigar::from("3H10M").truncate_query(0), Cigar::from("3H10S"));
+            assert_eq!(Cigar::from("5S10M2H").truncate_query(0), Cigar::from("15S2H"));
+            assert_eq!(Cigar::from("10M5D10M").truncate_query(0), Cigar::from("20S"));
+        }
+        #[test]
+        fn test_aligned_positions_in_window(){
+            let cig = Cigar::from("2S5M2D3M100N5M");
+            let positions: Vec<i64> = cig.aligned_positions_in_window(100, 103, 112).collect();
+            assert_eq!(positions, vec![103, 104, 107, 108, 109]);
+            let positions: Vec<i64> = cig.aligned_positions_in_window(100, 200, 300).collect();
+            assert_eq!(positions, vec![210, 211, 212, 213, 214]);
+            assert_eq!(cig.aligned_positions_in_window(100, 120, 150).count(), 0);
+        }
+        #[test]
+        fn test_describe(){
+            let cig = Cigar::from("12S100M200N35M3D3M300N12M");
+            assert_eq!(cig.describe(), "162bp read, 2 introns, 12bp soft-clipped, 1 deletion (3bp)");
+            assert_eq!(Cigar::from("50M").describe(), "50bp read");
+            assert_eq!(Cigar::from("5H20M1I10M2I").describe(), "33bp read, 5bp hard-clipped, 2 insertions (3bp)");
+        }
+        #[test]
+        #[cfg(feature = "std")]
+        fn test_clips_consistent_with_strand(){
+            let three_prime = Cigar::from("100M45S");
+            let five_prime = Cigar::from("45S100M");
+            assert!(three_prime.clips_consistent_with_strand(&Strand::Plus));
+            assert!(!three_prime.clips_consistent_with_strand(&Strand::Minus));
+            assert!(five_prime.clips_consistent_with_strand(&Strand::Minus));
+            assert!(!five_prime.clips_consistent_with_strand(&Strand::Plus));
+            assert!(five_prime.clips_consistent_with_strand(&Strand::NA));
+            assert!(!Cigar::from("2H5S100M").clips_consistent_with_strand(&Strand::Plus));
+            assert!(!Cigar::from("100M5S2H").clips_consistent_with_strand(&Strand::Minus));
+            assert!(Cigar::from("2H100M5S2H").clips_consistent_with_strand(&Strand::Plus));
+        }
+        #[test]
+        fn test_add_to_coverage(){
+            let mut coverage = [0u32; 10];
+            Cigar::from("2S3M2D3M").add_to_coverage(100, 102, &mut coverage);
+            Cigar::from("4M").add_to_coverage(109, 102, &mut coverage);
+            assert_eq!(coverage, [1, 0, 0, 1, 1, 1, 0, 1, 1, 1]);
+        }
+        #[test]
+        fn test_covers_with_flank(){
+            let cig = Cigar::from("10M2D20M");
+            assert!(cig.covers_with_flank(100, 120, 5));
+            // only 3 bases after the deletion
+            assert!(!cig.covers_with_flank(100, 115, 5));
+            assert!(cig.covers_with_flank(100, 115, 3));
+            // inside the deletion
+            assert!(!cig.covers_with_flank(100, 110, 0));
+            assert!(!cig.covers_with_flank(100, 150, 0));
+        }
+        #[test]
+        fn test_query_boundaries(){
+            let cig = Cigar::from("3H2S10M110N5M3I2D4M");
+            assert_eq!(cig.query_boundaries(), vec![0, 2, 12, 12, 17, 20, 20, 24]);
+        }
+        #[test]
+        fn test_indels_as_alleles(){
+            let reference = b"ACGTACGTACGTACGT";
+            let cig = Cigar::from("1S3M2I2M2D3M");
+            let results = cig.indels_as_alleles(2, reference, b"TGTATTCGCGT");
+            assert_eq!(results, vec![
+                (4, b"A".to_vec(), b"ATT".to_vec()),
+                (6, b"GTA".to_vec(), b"G".to_vec()),
+            ]);
+            // no anchor base before a leading insertion at 0
+            assert!(Cigar::from("2I3M").indels_as_alleles(0, reference, b"TTACG").is_empty());
+        }
+        #[test]
+        fn test_bridge_short_deletions(){
+            let cig = Cigar::from("10M2D10M110N5M8D5M");
+            assert_eq!(cig.bridge_short_deletions(2), Cigar::from("22M110N5M8D5M"));
+            assert_eq!(cig.bridge_short_deletions(10), Cigar::from("22M110N18M"));
+            assert_eq!(cig.bridge_short_deletions(0), cig);
+        }
+        #[test]
+        fn test_end_of_aln_exclusive(){
+            let cig = Cigar::from("50M100N75M");
+            assert_eq!(cig.get_end_of_aln(&1000), 1225);
+            assert_eq!(cig.reference_last_position(1000), 1224);
+        }
+        #[test]
+        fn test_unclipped_start_end(){
+            let cig = Cigar::from("3H5S20M100N10M7S");
+            assert_eq!(cig.unclipped_start(1000), 995);
+            assert_eq!(cig.unclipped_end(1000), 1137);
+            let cig = Cigar::from("20M");
+            assert_eq!(cig.unclipped_start(1000), 1000);
+            assert_eq!(cig.unclipped_end(1000), 1020);
+        }
+        #[test]
+        fn test_iter_rev(){
+            let cig = Cigar::from("5S20M3I");
+            let ops: Vec<&CigarOperation> = cig.iter_rev().collect();
+            assert_eq!(ops, vec![&CigarOperation::Insertion(3), &CigarOperation::Match(20), &CigarOperation::Soft(5)]);
+            assert_eq!(cig.iter_rev().next_back(), Some(&CigarOperation::Soft(5)));
+        }
+        #[test]
+        fn test_checked_reference_end(){
+            let cig = Cigar::from("50M100N75M");
+            assert_eq!(cig.checked_reference_end(1000), Some(1225));
+            assert_eq!(cig.checked_reference_end(i64::MAX - 100), None);
+            let cig = Cigar::from("9223372036854775807M10M");
+            assert_eq!(cig.checked_reference_end(0), None);
+        }
+        #[test]
+        fn test_soft_clips(){
+            assert_eq!(Cigar::from("5S100M12S").soft_clips(), (5, 12));
+            assert_eq!(Cigar::from("2H5S100M").soft_clips(), (5, 0));
+            assert_eq!(Cigar::from("100M").soft_clips(), (0, 0));
+        }
+        #[test]
+        fn test_op_at_query(){
+            let cig = Cigar::from("2H5S20M100N3I10M");
+            assert_eq!(cig.op_at_query(0, 0), Some(&CigarOperation::Soft(5)));
+            assert_eq!(cig.op_at_query(0, 5), Some(&CigarOperation::Match(20)));
+            assert_eq!(cig.op_at_query(0, 26), Some(&CigarOperation::Insertion(3)));
+            assert_eq!(cig.op_at_query(0, 37), Some(&CigarOperation::Match(10)));
+            assert_eq!(cig.op_at_query(0, 38), None);
+            assert_eq!(cig.op_at_query(10, 5), None);
+        }
+        #[test]
+        fn test_summary(){
+            let summary = Cigar::from("3H5S20M100N10M2I5M3D4M").summary();
+            assert_eq!(summary, CigarSummary{
+                query_length: 46, reference_span: 142, junction_count: 1, soft_clipped: 5, hard_clipped: 3,
+                insertion_count: 1, insertion_bases: 2, deletion_count: 1, deletion_bases: 3,
+            });
+        }
+        #[test]
+        #[cfg(feature = "serde")]
+        fn test_summary_json(){
+            let json = Cigar::from("5S20M100N10M").summary_json();
+            assert!(json.starts_with("{\"query_length\":35,\"reference_span\":130,\"junction_count\":1,"));
+        }
+        #[test]
+        fn test_soft_clip_to_intron(){
+            let cig = Cigar::from("10S100M45S2H");
+            assert_eq!(cig.soft_clip_to_intron(End::Three, 500), Cigar::from("10S100M500N45M2H"));
+            assert_eq!(cig.soft_clip_to_intron(End::Five, 500), Cigar::from("10M500N100M45S2H"));
+            let cig = Cigar::from("100M");
+            assert_eq!(cig.soft_clip_to_intron(End::Three, 500), cig);
+        }
+        #[test]
+        fn test_aligned_bases_in_window(){
+            let cig = Cigar::from("2S5M2D3M100N5M");
+            assert_eq!(cig.aligned_bases_in_window(100, 103, 112), 5);
+            assert_eq!(cig.aligned_bases_in_window(100, 0, 1000), 13);
+            assert_eq!(cig.aligned_bases_in_window(100, 120, 150), 0);
+        }
+        #[test]
+        fn test_diff(){
+            let truth = Cigar::from("35M110N45M");
+            let aligned = Cigar::from("35M108D47M2S");
+            let results = truth.diff(&aligned);
+            assert_eq!(results, vec![
+                CigarDiff::Mismatch{ index: 1, expected: CigarOperation::Nskipped(110), got: CigarOperation::Deletion(108) },
+                CigarDiff::Mismatch{ index: 2, expected: CigarOperation::Match(45), got: CigarOperation::Match(47) },
+                CigarDiff::Extra{ index: 3, got: CigarOperation::Soft(2) },
+            ]);
+            assert_eq!(format!("{}", results[0]), "position 1: expected N(110), got D(108)");
+            assert!(truth.diff(&truth).is_empty());
+        }
+        #[test]
+        fn test_map_reference_interval(){
+            let cig = Cigar::from("5S10M2I5M3D10M");
+            let results = cig.map_reference_interval(100, 0, 105, 122);
+            assert_eq!(results, vec![
+                (105..110, 10..15),
+                (110..110, 15..17),
+                (110..115, 17..22),
+                (115..118, 22..22),
+                (118..122, 22..26),
+            ]);
+            // an insertion right at the start of the interval is not inside it
+            assert_eq!(cig.map_reference_interval(100, 0, 110, 112), vec![(110..112, 17..19)]);
+        }
+        #[test]
+        fn test_trim_terminal_deletions(){
+            let cig = Cigar::from("5S3D20M2D10M4D2H");
+            assert!(cig.has_terminal_deletion());
+            assert_eq!(cig.trim_terminal_deletions(), (Cigar::from("5S20M2D10M2H"), 3));
+            let cig = Cigar::from("20M2D10M");
+            assert!(!cig.has_terminal_deletion());
+            assert_eq!(cig.trim_terminal_deletions(), (cig.clone(), 0));
+            assert!(!Cigar::from("*").has_terminal_deletion());
+        }
+        #[test]
+        fn test_to_bam_u32(){
+            let cig = Cigar::from("5S35M110N2I3D1H");
+            assert_eq!(cig.to_bam_u32(), vec![5 << 4 | 4, 35 << 4, 110 << 4 | 3, 2 << 4 | 1, 3 << 4 | 2, 1 << 4 | 5]);
+            assert!(Cigar::from("*").to_bam_u32().is_empty());
+        }
+        #[test]
+        fn test_from_bam_u32(){
+            let cig = Cigar::from("5S35M110N2I3D4=1X1H");
+            assert_eq!(Cigar::from_bam_u32(&cig.to_bam_u32()).unwrap(), cig);
+            assert_eq!(Cigar::from_bam_u32(&[35 << 4, 10 << 4 | 9]), Err(CigarError::InvalidOpCode));
+            assert_eq!(Cigar::from_bam_u32(&[]).unwrap(), Cigar{ cigar: vec![] });
+        }
+        #[test]
+        fn test_seq_match_mismatch(){
+            let cig = Cigar::from_str("5=1X4=100N10M").unwrap();
+            assert_eq!(format!("{}", cig), "5=1X4=100N10M");
+            assert_eq!(cig.get_end_of_aln(&0), 120);
+            assert_eq!(cig.get_reference_cover(0), vec![0, 5, 5, 6, 6, 10, 110, 120]);
+        }
+        #[test]
+        fn test_segments(){
+            let cig = Cigar::from("5S10M2D10M3I5M500N20M");
+            assert_eq!(cig.segments(100, 10), vec![100..127, 627..647]);
+            assert_eq!(cig.segments(100, 0), vec![100..110, 112..127, 627..647]);
+            assert_eq!(cig.segments(100, 500), vec![100..647]);
+        }
+        #[test]
+        fn test_validate_dimensions(){
+            let cig = Cigar::from("5S35M110N45M");
+            assert_eq!(cig.validate_dimensions(100, 1000, 85), Ok(()));
+            assert_eq!(cig.validate_dimensions(-1, 1000, 85), Err(CigarError::NegativeStart(-1)));
+            assert_eq!(cig.validate_dimensions(900, 1000, 85), Err(CigarError::BeyondReference{ end: 1090, ref_len: 1000 }));
+            assert_eq!(cig.validate_dimensions(100, 1000, 80), Err(CigarError::QueryLengthMismatch{ cigar: 85, query: 80 }));
+        }
+        #[test]
+        fn test_to_minus_strand(){
+            let cig = Cigar::from("5S35M110N45M2I");
+            assert_eq!(cig.reverse(), Cigar::from("2I45M110N35M5S"));
+            assert_eq!(cig.to_minus_strand(100, 1000), (Cigar::from("2I45M110N35M5S"), 710));
+        }
+        #[test]
+        #[cfg(feature = "std")]
+        fn test_base_counts_by_op(){
+            let counts = Cigar::from("5S35M110N45M2I200N3M").base_counts_by_op();
+            assert_eq!(counts.len(), 4);
+            assert_eq!(counts[&'N'], 310);
+            assert_eq!(counts[&'M'], 83);
+            assert_eq!(counts[&'S'], 5);
+            assert!(Cigar::from("*").base_counts_by_op().is_empty());
+        }
+        #[test]
+        fn test_supports_junction(){
+            let cig = Cigar::from("35M110N45M3D10M200N5M");
+            assert!(cig.supports_junction(500, 535, 645));
+            assert!(cig.supports_junction(500, 703, 903));
+            assert!(!cig.supports_junction(500, 535, 646));
+            assert!(!cig.supports_junction(500, 690, 693));
+            // the junction is not anchored on the right side
+            assert!(!Cigar::from("35M110N2I45M").supports_junction(500, 535, 645));
+        }
+        #[test]
+        #[cfg(feature = "std")]
+        fn test_clip_length_end(){
+            let cig = Cigar::from("10H5S100M20S30H");
+            assert_eq!(cig.clip_length_end(&Strand::Plus), Some(50));
+            assert_eq!(cig.clip_length_end(&Strand::Minus), Some(15));
+            assert_eq!(Cigar::from("100M").clip_length_end(&Strand::Plus), None);
+        }
+        #[test]
+        fn test_is_monotonic_reference(){
+            assert!(Cigar::from("5S35M110N45M").is_monotonic_reference());
+            assert!(Cigar::from(vec![CigarOperation::Match(10), CigarOperation::Insertion(-2)]).is_monotonic_reference());
+            assert!(!Cigar::from(vec![CigarOperation::Match(10), CigarOperation::Deletion(-2)]).is_monotonic_reference());
+        }
+        #[test]
+        fn test_reference_prefix(){
+            let cig = Cigar::from("5S20M2I10M110N15M3S2H");
+            // inside a match
+            assert_eq!(cig.reference_prefix(100, 110), Cigar::from("5S10M40S2H"));
+            // at the block boundary, the insertion is left to the soft clip
+            assert_eq!(cig.reference_prefix(100, 120), Cigar::from("5S20M30S2H"));
+            // inside the intron
+            assert_eq!(cig.reference_prefix(100, 140), Cigar::from("5S20M2I10M10N18S2H"));
+            assert_eq!(cig.reference_prefix(100, 1000), cig);
+            // inside the last block
+            assert_eq!(cig.reference_prefix(100, 250), Cigar::from("5S20M2I10M110N10M8S2H"));
+            assert_eq!(Cigar::from("10M").reference_prefix(0, 5), Cigar::from("5M5S"));
+            assert_eq!(Cigar::from("10M5D").reference_prefix(0, 12), Cigar::from("10M2D"));
+        }
+        #[test]
+        fn test_query_prefix(){
+            let cig = Cigar::from("5S20M2D10M110N15M");
+            assert_eq!(cig.query_prefix(15), (Cigar::from("5S10M35S"), 10));
+            assert_eq!(cig.query_prefix(30), (Cigar::from("5S20M2D5M20S"), 27));
+            assert_eq!(cig.query_prefix(100), (cig.clone(), 157));
+            assert_eq!(Cigar::from("10M5D10M").query_prefix(0), (Cigar::from("20S"), 0));
+        }
+        #[test]
+        fn test_write_to(){
+            let mut buf = String::new();
+            Cigar::from("5S35M110N45M").write_to(&mut buf);
+            buf.push('\t');
+            Cigar::from("10=1X").write_to(&mut buf);
+            assert_eq!(buf, "5S35M110N45M\t10=1X");
+            assert_eq!(Cigar::from("*").to_string(), "*");
+        }
+        #[test]
+        fn test_count_arrays(){
+            let cig = Cigar::from("5S35M110N10M2I5M200N3=1X2H");
+            assert_eq!(cig.op_count_array(), [3, 1, 0, 2, 1, 1, 0, 1, 1]);
+            assert_eq!(cig.base_count_array(), [50, 2, 0, 310, 5, 2, 0, 3, 1]);
+        }
+        #[test]
+        fn test_introns_as_deletions(){
+            let cig = Cigar::from("35M110N2D45M3N10M");
+            assert_eq!(cig.introns_as_deletions(), Cigar::from("35M112D45M3D10M"));
+        }
+        #[test]
+        fn test_first_aligned_reference_position(){
+            assert_eq!(Cigar::from("5S35M110N45M").first_aligned_reference_position(100), Some(100));
+            assert_eq!(Cigar::from("5S3D35M").first_aligned_reference_position(100), Some(103));
+            assert_eq!(Cigar::from("5S3I").first_aligned_reference_position(100), None);
+        }
+        #[test]
+        fn test_last_aligned_reference_position(){
+            assert_eq!(Cigar::from("5S35M110N45M5S").last_aligned_reference_position(100), Some(290));
+            let cig = Cigar::from("35M4D");
+            assert_eq!(cig.last_aligned_reference_position(100), Some(135));
+            assert_eq!(cig.get_end_of_aln(&100), 139);
+            assert_eq!(Cigar::from("5S3I").last_aligned_reference_position(100), None);
+        }
+        #[test]
+        fn test_concat(){
+            assert_eq!(Cigar::from("5S10M").concat(&Cigar::from("5M2I")), Cigar::from("5S15M2I"));
+            assert_eq!(Cigar::from("10M").concat(&Cigar::from("2D5M")), Cigar::from("10M2D5M"));
+            let x = Cigar::from("5S10M2I");
+            assert_eq!(Cigar::default().concat(&x), x);
+            assert_eq!(x.concat(&Cigar::default()), x);
+        }
+        #[test]
+        fn test_concat_associative(){
+            // deterministic pseudo random operation lists, small alphabet so junction merges are frequent
+            let mut seed: u64 = 42;
+            let mut next = move |n: u64| { seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); (seed >> 33) % n };
+            let mut random_cigar = || -> Cigar {
+                let ops: Vec<CigarOperation> = (0..next(4))
+                    .map(|_| CigarOperation::from_symbol(['M', 'I', 'D'][next(3) as usize], next(5) as i64))
+                    .collect();
+                Cigar::from(ops)
+            };
+            for _ in 0..500{
+                let (a, b, c) = (random_cigar(), random_cigar(), random_cigar());
+                assert_eq!(a.concat(&b).concat(&c), a.concat(&b.concat(&c)));
+            }
+            let chunks = vec![Cigar::from("5S10M"), Cigar::from("5M"), Cigar::from("110N"), Cigar::from("20M5S")];
+            let assembled = chunks.into_iter().fold(Cigar::default(), |a, b| a.concat(&b));
+            assert_eq!(assembled, Cigar::from("5S15M110N20M5S"));
+        }
+        #[test]
+        fn test_restrict_to_window(){
+            let cig = Cigar::from("5S10M2I5M3D10M100N10M5S");
+            let slice = cig.restrict_to_window(100, 105, 125).unwrap();
+            assert_eq!(slice, WindowSlice{ cigar: Cigar::from("5M2I5M3D7M"), new_ref_start: 105, query_start: 10, query_end: 29 });
+            assert_eq!(slice.cigar.get_read_length_from_cigar(), slice.query_end - slice.query_start);
+            // the window starts in the deletion and ends in the intron
+            let slice = cig.restrict_to_window(100, 116, 200).unwrap();
+            assert_eq!(slice, WindowSlice{ cigar: Cigar::from("10M"), new_ref_start: 118, query_start: 22, query_end: 32 });
+            assert_eq!(cig.restrict_to_window(100, 130, 200), None);
+        }
+        #[test]
+        fn test_add(){
+            assert_eq!(Cigar::from("5S10M") + Cigar::from("5M2I"), Cigar::from("5S15M2I"));
+            let mut cig = Cigar::from("5S10M");
+            cig += Cigar::from("110N20M");
+            assert_eq!(cig, Cigar::from("5S10M110N20M"));
+            let chunks = vec![Cigar::from("5S10M"), Cigar::from("5M"), Cigar::from("110N20M")];
+            assert_eq!(chunks.into_iter().sum::<Cigar>(), Cigar::from("5S15M110N20M"));
+            assert_eq!(Vec::<Cigar>::new().into_iter().sum::<Cigar>(), Cigar::default());
+        }
+        #[test]
+        fn test_covered_bins(){
+            let cig = Cigar::from("5S50M1000N30M2D10M");
+            assert_eq!(cig.covered_bins(90, 100), vec![0, 1, 11]);
+            assert_eq!(cig.covered_bins(0, 1000), vec![0, 1]);
+            assert!(cig.covered_bins(0, 0).is_empty());
+            assert!(cig.covered_bins(0, -10).is_empty());
+        }
+        #[test]
+        fn test_soft_clipped_sequences(){
+            let cig = Cigar::from("2H3S5M2S");
+            assert_eq!(cig.soft_clipped_sequences(b"AAACCCCCGG"), (Some(&b"AAA"[..]), Some(&b"GG"[..])));
+            let cig = Cigar::from("5M2S");
+            assert_eq!(cig.soft_clipped_sequences(b"CCCCCGG"), (None, Some(&b"GG"[..])));
+        }
+        #[test]
+        fn test_insertions_query_positions(){
+            let cig = Cigar::from("5S10M2I5M100N5M3I5M");
+            assert_eq!(cig.insertions_query_positions(100, 0), vec![(15, 110, 2), (27, 220, 3)]);
+        }
+        #[test]
+        fn test_count_mismatches(){
+            let ref_seq = b"GGACGTTTTTACGT";
+            let cig = Cigar::from("2S4M4D4M");
+            assert_eq!(cig.count_mismatches(2, ref_seq, b"TTACGAACGT", 0), 1);
+            assert_eq!(cig.count_mismatches(2, ref_seq, b"TTacgtACGA", 0), 1);
+        }
+        #[test]
+        fn test_edit_distance(){
+            let ref_seq = b"GGACGTTTTTACGTCCCCCCACGT";
+            let cig = Cigar::from("2S4M4D2M1I2M6N4M1H");
+            assert_eq!(cig.edit_distance(2, ref_seq, b"TTACGAACAGTACGT", 0), 6);
+        }
+        #[test]
+        fn test_drop_hard_clips(){
+            let cig = Cigar::from("5H10S50M3H");
+            assert_eq!(cig.drop_hard_clips(), Cigar::from("10S50M"));
+            assert_eq!(cig.drop_hard_clips().get_read_length_from_cigar(), cig.get_read_length_from_cigar());
+        }
+        #[test]
+        fn test_pileup_events(){
+            let cig = Cigar::from("1S2M1I1M2D1M10N1M");
+            let events: Vec<PileupEvent> = cig.pileup_events(100, 0).collect();
+            assert_eq!(events, vec![
+                PileupEvent::Match{ ref_pos: 100, query_pos: 1 },
+                PileupEvent::Match{ ref_pos: 101, query_pos: 2 },
+                PileupEvent::Ins{ ref_anchor: 102, query_pos: 3, len: 1 },
+                PileupEvent::Match{ ref_pos: 102, query_pos: 4 },
+                PileupEvent::Del{ ref_pos: 103 },
+                PileupEvent::Del{ ref_pos: 104 },
+                PileupEvent::Match{ ref_pos: 105, query_pos: 5 },
+                PileupEvent::Skip{ start: 106, end: 116 },
+                PileupEvent::Match{ ref_pos: 116, query_pos: 6 },
+            ]);
+        }
+        #[test]
+        fn test_splice_skeleton(){
+            assert_eq!(Cigar::from("35M110N45M").splice_skeleton(100), vec![(100, 135), (245, 290)]);
+            assert_eq!(Cigar::from("5S10M2D5M20N5M5S").splice_skeleton(0), vec![(0, 17), (37, 42)]);
+        }
+        #[test]
+        fn test_is_chimeric_candidate(){
+            let cig = Cigar::from("30S70M");
+            assert!(cig.is_chimeric_candidate(0.2, 20));
+            assert!(!cig.is_chimeric_candidate(0.4, 20));
+            assert!(!cig.is_chimeric_candidate(0.2, 40));
+            assert!(Cigar::from("70M30S").is_chimeric_candidate(0.2, 20));
+            assert!(!Cigar::from("5S90M5S").is_chimeric_candidate(0.01, 10));
+        }
+        #[test]
+        fn test_operations_mut(){
+            let mut cig = Cigar::from("10M2I5M");
+            if let CigarOperation::Match(n) = &mut cig.operations_mut()[0]{
+                *n -= 1;
+            }
+            cig.operations_mut()[1] = CigarOperation::Match(2);
+            assert_eq!(cig.operations(), &[CigarOperation::Match(9), CigarOperation::Match(2), CigarOperation::Match(5)]);
+            assert_eq!(cig.normalize(), Cigar::from("16M"));
+        }
+        #[test]
+        fn test_gap_totals(){
+            let cig = Cigar::from("5S10M2D5M3I5M100N5M1I5M");
+            assert_eq!(cig.reference_gap_total(), 102);
+            assert_eq!(cig.query_gap_total(), 4);
+        }
+        #[test]
+        fn test_drop_weak_junctions(){
+            let cig = Cigar::from("3M100N40M2D5M200N30M");
+            assert_eq!(cig.drop_weak_junctions(5), Cigar::from("3M100D40M2D5M200N30M"));
+            assert_eq!(cig.drop_weak_junctions(6), Cigar::from("3M100D40M2D5M200D30M"));
+            assert_eq!(cig.drop_weak_junctions(3), cig);
+        }
+        #[test]
+        fn test_to_cs_tag(){
+            let ref_seq = b"ACGTAGTCCCCAGTTGCA";
+            let cig = Cigar::from("2S3M1I2M8N2M2D1M");
+            let query_seq = b"NNACGGTAGCA";
+            assert_eq!(cig.to_cs_tag(0, ref_seq, query_seq), ":3+g:2~gt8ag*tg*tc-gc:1");
+            // the motif of a 1 base intron does not read the flanking exons
+            assert_eq!(Cigar::from("2M1N2M").to_cs_tag(0, b"ACGTA", b"ACTA"), ":2~gn1ng:2");
+        }
+        #[test]
+        fn test_from_cs_tag(){
+            assert_eq!(Cigar::from_cs_tag(":3+g:2~gt8ag*tg*tc-gc:1").unwrap(), Cigar::from("3M1I2M8N2M2D1M"));
+            assert_eq!(Cigar::from_cs_tag("cs:Z:=ACG*ag=T-a:2").unwrap(), Cigar::from("5M1D2M"));
+            assert_eq!(Cigar::from_cs_tag(":3+"), Err(CigarError::Parse));
+            assert_eq!(Cigar::from_cs_tag(":3*a"), Err(CigarError::Parse));
+            assert_eq!(Cigar::from_cs_tag("3M"), Err(CigarError::Parse));
+
+            let ref_seq = b"ACGTAGTCCCCAGTTGCA";
+            let cig = Cigar::from("3M1I2M8N2M2D1M");
+            assert_eq!(Cigar::from_cs_tag(&cig.to_cs_tag(0, ref_seq, b"ACGGTAGCA")).unwrap(), cig);
+        }
+        #[test]
+        fn test_reference_coverage_rle(){
+            let cig = Cigar::from("5S10M2D5M1I5M100N2D5M5S");
+            assert_eq!(cig.reference_coverage_rle(100), vec![(10, true), (2, false), (10, true), (102, false), (5, true)]);
+        }
+        #[test]
+        fn test_overlap_with_intervals(){
+            let cig = Cigar::from("5S20M100N10M2D10M");
+            let exons = [(90, 110), (200, 1000)];
+            assert_eq!(cig.overlap_with_intervals(100, &exons), 10 + 10 + 10);
+            assert_eq!(cig.overlap_with_intervals(100, &[(115, 225), (231, 233)]), 5 + 5 + 1);
+            assert_eq!(cig.overlap_with_intervals(100, &[]), 0);
+        }
+        #[test]
+        fn test_junction_ids(){
+            assert_eq!(Cigar::from("35M110N45M").junction_ids(100, "chr1"), vec!["chr1:135-245"]);
+            assert_eq!(Cigar::from("10M5N2D10M20N5M").junction_ids(0, "2"), vec!["2:10-15", "2:27-47"]);
+            assert!(Cigar::from("10M").junction_ids(0, "2").is_empty());
+        }
+        #[test]
+        fn test_split_on_deletion(){
+            let cig = Cigar::from("5S10M50D10M2D10M5S");
+            assert_eq!(cig.split_on_deletion(100, 20), vec![
+                (100, Cigar::from("5S10M25S")),
+                (160, Cigar::from("15S10M2D10M5S")),
+            ]);
+            assert_eq!(cig.split_on_deletion(100, 50), vec![(100, cig.clone())]);
+            let cig = Cigar::from("3H10M50D5M1I5M4H");
+            assert_eq!(cig.split_on_deletion(0, 20), vec![
+                (0, Cigar::from("3H10M11S4H")),
+                (60, Cigar::from("3H10S5M1I5M4H")),
+            ]);
+            // an insertion next to the split is clipped with the rest of the flanking query
+            assert_eq!(Cigar::from("10M50D2I10M").split_on_deletion(0, 20), vec![
+                (0, Cigar::from("10M12S")),
+                (60, Cigar::from("12S10M")),
+            ]);
+            assert_eq!(Cigar::from("10M3I50D10M").split_on_deletion(0, 20), vec![
+                (0, Cigar::from("10M13S")),
+                (60, Cigar::from("13S10M")),
+            ]);
+        }
+        #[test]
+        fn test_aligned_block_count(){
+            assert_eq!(Cigar::from("10M2D5M100N5M1I5M").aligned_block_count(), 2);
+            assert_eq!(Cigar::from("5S3=1X6M100N5M10N5M5S").aligned_block_count(), 3);
+            assert_eq!(Cigar::from("10M100N10N10M").aligned_block_count(), 2);
+            assert_eq!(Cigar::from("10S").aligned_block_count(), 0);
+        }
+        #[test]
+        fn test_prepend_append_soft_clip(){
+            assert_eq!(Cigar::from("2H5S10M").prepend_soft_clip(3), Cigar::from("2H8S10M"));
+            assert_eq!(Cigar::from("10M").prepend_soft_clip(3), Cigar::from("3S10M"));
+            assert_eq!(Cigar::from("10M5S2H").append_soft_clip(3), Cigar::from("10M8S2H"));
+            assert_eq!(Cigar::from("2H10M2H").append_soft_clip(3), Cigar::from("2H10M3S2H"));
+            assert_eq!(Cigar::from("10M").append_soft_clip(0), Cigar::from("10M"));
+        }
+        #[test]
+        fn test_exonic_reference_length(){
+            let cig = Cigar::from("5S10M2D3=1X100N5M2I5M5S");
+            assert_eq!(cig.exonic_reference_length(), 26);
+            assert_eq!(cig.get_end_of_aln(&0), 126);
+        }
+        #[test]
+        fn test_truncate_at_reference(){
+            let cig = Cigar::from("5S20M4D10M110N15M3S");
+            // inside a match
+            assert_eq!(cig.truncate_at_reference(100, 110), Cigar::from("5S10M38S"));
+            // inside a deletion
+            assert_eq!(cig.truncate_at_reference(100, 122), Cigar::from("5S20M2D28S"));
+            // inside an intron
+            assert_eq!(cig.truncate_at_reference(100, 144), Cigar::from("5S20M4D10M10N18S"));
+            assert_eq!(cig.truncate_at_reference(100, 144).get_read_length_from_cigar(), cig.get_read_length_from_cigar());
+            // inside the last match
+            assert_eq!(cig.truncate_at_reference(100, 254), Cigar::from("5S20M4D10M110N10M8S"));
+            // inside a trailing deletion
+            assert_eq!(Cigar::from("5S20M4D").truncate_at_reference(100, 122), Cigar::from("5S20M2D"));
+        }
+        #[test]
+        fn test_span_of(){
+            let cig = Cigar::from("2H5S10M2I3D100N");
+            assert_eq!((0..7).map(|i| cig.ref_span_of(i)).collect::<Vec<_>>(), vec![Some(0), Some(0), Some(10), Some(0), Some(3), Some(100), None]);
+            assert_eq!((0..7).map(|i| cig.query_span_of(i)).collect::<Vec<_>>(), vec![Some(0), Some(5), Some(10), Some(2), Some(0), Some(0), None]);
+        }
+        #[test]
+        fn test_reference_offset_table(){
+            let cig = Cigar::from("5S10M2I2D5M100N5M");
+            let table = cig.reference_offset_table();
+            assert_eq!(table, vec![0, 0, 10, 10, 12, 17, 117]);
+            // operation covering the reference offset 15
+            assert_eq!(table.partition_point(|offset| *offset <= 15) - 1, 4);
+        }
+        #[test]
+        fn test_query_to_ref_indexed(){
+            let cig = Cigar::from("5S10M2I2D5M100N5M3H");
+            let tables = cig.offset_tables();
+            assert_eq!(tables.query, vec![0, 5, 15, 17, 17, 22, 22, 27]);
+            assert_eq!(tables.reference, cig.reference_offset_table());
+            let mapped: Vec<Option<i64>> = [4, 5, 14, 15, 17, 21, 22, 26, 27].iter()
+                .map(|q| cig.query_to_ref_indexed(&tables, 100, 0, *q))
+                .collect();
+            assert_eq!(mapped, vec![None, Some(100), Some(109), None, Some(112), Some(116), Some(217), Some(221), None]);
+        }
+        #[test]
+        fn test_collapse_complex_indels(){
+            let cig = Cigar::from("10M2I3M3D10M");
+            assert_eq!(cig.collapse_complex_indels(5), Cigar::from("10M1D15M"));
+            assert_eq!(cig.collapse_complex_indels(3), cig);
+            assert_eq!(Cigar::from("5S10M2I1M2I1M1D10M5S").collapse_complex_indels(2), Cigar::from("5S10M3I13M5S"));
+            assert_eq!(Cigar::from("10M2I2D10M").collapse_complex_indels(1), Cigar::from("22M"));
+            assert_eq!(Cigar::from("10M2I1M100N1M2D10M").collapse_complex_indels(5), Cigar::from("10M2I1M100N1M2D10M"));
+        }
+        #[test]
+        fn test_from_str_normalized(){
+            assert_eq!(Cigar::from_str_normalized("0M150M").unwrap(), Cigar::from("150M"));
+            assert_eq!(Cigar::from_str("0M150M").unwrap().into_operations().len(), 2);
+            assert_eq!(Cigar::from_str_normalized("5S10M0I5M"), Ok(Cigar::from("5S15M")));
+            assert_eq!(Cigar::from_str_normalized("10K"), Err(CigarError::Parse));
+        }
+        #[test]
+        fn test_reference_midpoint(){
+            assert_eq!(Cigar::from("5S10M100N10M").reference_midpoint(100), 160);
+            assert_eq!(Cigar::from("11M").reference_midpoint(0), 5);
+        }
+        #[test]
+        fn test_operations_in_reference_window(){
+            let cig = Cigar::from("5S10M2I5M3D5M100N5M5S");
+            assert_eq!(cig.operations_in_reference_window(100, 105, 116), vec![
+                (CigarOperation::Match(10), 100..110),
+                (CigarOperation::Insertion(2), 110..110),
+                (CigarOperation::Match(5), 110..115),
+                (CigarOperation::Deletion(3), 115..118),
+            ]);
+            assert_eq!(cig.operations_in_reference_window(100, 110, 111), vec![(CigarOperation::Match(5), 110..115)]);
+            assert_eq!(cig.operations_in_reference_window(100, 150, 160), vec![(CigarOperation::Nskipped(100), 123..223)]);
+            assert!(cig.operations_in_reference_window(100, 0, 100).is_empty());
+        }
+        #[test]
+        fn test_repair(){
+            let (cig, actions) = Cigar::from("5S2H2D3I10M0I5M2H4S").repair();
+            assert_eq!(cig, Cigar::from("2H8S15M4S2H"));
+            assert_eq!(actions, vec![
+                RepairAction::DroppedZeroLength(1),
+                RepairAction::StrippedDeletion{ end: End::Five, length: 2 },
+                RepairAction::InsertionToSoftClip{ end: End::Five, length: 3 },
+                RepairAction::MovedHardClip(End::Five),
+                RepairAction::MovedHardClip(End::Three),
+                RepairAction::MergedAdjacent,
+            ]);
+            let cig = Cigar::from("5S10M2I5M5S");
+            assert_eq!(cig.repair(), (cig.clone(), Vec::new()));
+            // rebuilding the clips is not a merge
+            assert_eq!(Cigar::from("2H5S3H10M").repair(), (Cigar::from("5H5S10M"), vec![RepairAction::MovedHardClip(End::Five)]));
+        }
+        #[test]
+        fn test_block_entries(){
+            let cig = Cigar::from("5S10M2D5M100N5M");
+            assert_eq!(cig.block_entries(100, "read1"), vec![(100, 110, "read1"), (112, 117, "read1"), (217, 222, "read1")]);
+        }
+        #[test]
+        fn test_uses_hard_clips(){
+            assert!(Cigar::from("30H70M").uses_hard_clips());
+            assert!(!Cigar::from("30S70M").uses_hard_clips());
+        }
+        #[test]
+        fn test_iter_match_blocks(){
+            let cig = Cigar::from("5S10M2I5M100N5M5S");
+            let blocks: Vec<(Range<i64>, Range<i64>)> = cig.iter_match_blocks(100, 0).collect();
+            assert_eq!(blocks, vec![(100..110, 5..15), (110..115, 17..22), (215..220, 22..27)]);
+        }
+        #[test]
+        fn test_total_clipped_bases(){
+            assert_eq!(Cigar::from("3H5S10M2I5M4S2H").total_clipped_bases(), 14);
+            assert_eq!(Cigar::from("10M").total_clipped_bases(), 0);
+        }
+        #[test]
+        fn test_flip_query_coordinate(){
+            let cig = Cigar::from("2H5S10M2I3M");
+            assert_eq!(cig.flip_query_coordinate(0), 20);
+            assert_eq!(cig.flip_query_coordinate(5), 15);
+            assert_eq!(cig.flip_query_coordinate(cig.flip_query_coordinate(7)), 7);
+        }
+        #[test]
+        fn test_contains_op(){
+            let cig = Cigar::from("5S10M2I5M100N5M");
+            assert!(cig.contains_op('I'));
+            assert!(cig.contains_op('N'));
+            assert!(!cig.contains_op('H'));
+            assert!(!cig.contains_op('D'));
+        }
+        #[test]
+        fn test_ref_to_query_detailed(){
+            let cig = Cigar::from("5S10M2I5M3D5M100N5M5S");
+            assert_eq!(cig.ref_to_query_detailed(100, 0, 99), RefMapResult::OutOfRange);
+            assert_eq!(cig.ref_to_query_detailed(100, 0, 100), RefMapResult::Aligned(5));
+            assert_eq!(cig.ref_to_query_detailed(100, 0, 110), RefMapResult::Aligned(17));
+            assert_eq!(cig.ref_to_query_detailed(100, 0, 116), RefMapResult::Deleted);
+            assert_eq!(cig.ref_to_query_detailed(100, 0, 150), RefMapResult::Skipped);
+            assert_eq!(cig.ref_to_query_detailed(100, 0, 227), RefMapResult::Aligned(31));
+            assert_eq!(cig.ref_to_query_detailed(100, 0, 228), RefMapResult::OutOfRange);
+        }
+        #[test]
+        fn test_to_eqx(){
+            let ref_seq = b"ACGTAGTCCCCAGTTGCA";
+            let cig = Cigar::from("2S5M1I5M");
+            assert_eq!(cig.to_eqx(0, ref_seq, b"NNACCTATGTCCC", 0), Cigar::from("2S2=1X2=1I5="));
+            // bases outside the reference are not mismatches, as in count_mismatches()
+            assert_eq!(Cigar::from("5M").to_eqx(15, ref_seq, b"GCTGG", 0), Cigar::from("2=1X2="));
+            assert_eq!(Cigar::from("5M").count_mismatches(15, ref_seq, b"GCTGG", 0), 1);
+            // runs of the same type are merged
+            assert_eq!(Cigar::from("5M5=").to_eqx(0, ref_seq, b"ACGTAGTCCC", 0), Cigar::from("10="));
+        }
+        #[test]
+        fn test_heap_size(){
+            assert_eq!(Cigar::default().heap_size(), 0);
+            let cig = Cigar::from("5S10M2I5M");
+            assert!(cig.heap_size() >= 4 * core::mem::size_of::<CigarOperation>());
+        }
+        #[test]
+        fn test_shrink_to_fit(){
+            let mut operations = Vec::with_capacity(100);
+            operations.push(CigarOperation::Match(10));
+            let mut cig = Cigar::from(operations);
+            cig.shrink_to_fit();
+            assert_eq!(cig.heap_size(), core::mem::size_of::<CigarOperation>());
+        }
+        #[test]
+        fn test_collect_unique_junctions(){
+            let reads = vec![
+                (Cigar::from("10M100N10M50N5M"), 100),
+                (Cigar::from("5M100N10M"), 105),
+                (Cigar::from("20M"), 0),
+                (Cigar::from("10M20N10M"), 0),
+            ];
+            assert_eq!(collect_unique_junctions(reads), vec![(10, 30), (110, 210), (220, 270)]);
+        }
+        #[test]
+        #[cfg(feature = "std")]
+        fn test_classify_junctions(){
+            let known: std::collections::HashSet<(i64, i64)> = [(110, 210), (300, 400)].into_iter().collect();
+            let cig = Cigar::from("10M100N10M50N5M");
+            assert_eq!(cig.classify_junctions(100, &known), vec![(110, 210, true), (220, 270, false)]);
+            assert!(Cigar::from("10M").classify_junctions(100, &known).is_empty());
+        }
+        #[test]
+        fn test_set_operation_length(){
+            let mut cig = Cigar::from("5S10M2I5M");
+            cig.set_operation_length(1, 9).unwrap();
+            assert_eq!(cig, Cigar::from("5S9M2I5M"));
+            cig.set_operation_length(2, 0).unwrap();
+            assert_eq!(cig, Cigar::from("5S14M"));
+            assert_eq!(cig.set_operation_length(1, -1), Err(CigarError::NegativeLength(-1)));
+            assert_eq!(cig.set_operation_length(2, 1), Err(CigarError::IndexOutOfRange(2)));
+            assert_eq!(cig, Cigar::from("5S14M"));
+        }
+        #[test]
+        fn test_coverage_bitset(){
+            let cig = Cigar::from("5S3M2D2M60N4M");
+            let bits = cig.coverage_bitset(100, 98, 70);
+            assert_eq!(bits.len(), 2);
+            // covered: 100..103, 105..107 and 167 (the rest of the last block is out of the region)
+            assert_eq!(bits[0], (1 << 2) | (1 << 3) | (1 << 4) | (1 << 7) | (1 << 8));
+            assert_eq!(bits[1], 1 << 5);
+            assert_eq!(cig.coverage_bitset(100, 0, 10), vec![0]);
+        }
+        #[test]
+        fn test_merged_blocks(){
+            let cig = Cigar::from("5S10M2D5M1I5M100N5M5S");
+            assert_eq!(cig.merged_blocks(100, 2), vec![100..122, 222..227]);
+            assert_eq!(cig.merged_blocks(100, 1), vec![100..110, 112..122, 222..227]);
+            assert_eq!(cig.merged_blocks(100, 100), vec![100..227]);
         }
-        #[test]
-        fn test_pos_none(){
-            let cig = Cigar::from("35M45M3I45M");
-            let results = cig.get_skipped_pos_on_ref(&500);
-            assert_eq!(results, None)
-        }   
-        #[test]
-        fn test_pos_2(){
-            let cig = Cigar::from("2S80M53373N169M");
-            let results = cig.get_skipped_pos_on_ref(&16946);
-            //assert_eq!(results, None)
-        }   
-        #[test]
-        fn test_match_1(){
-            let cig = Cigar::from("2S80M53373N169M");
-            let results = cig.does_it_match_an_intervall(&500, 550, 560);
-            //println!("{:?}", results);
-            assert_eq!(results, true)
-        }   
-        #[test]
-        fn test_match_outofbound(){
-            let cig = Cigar::from("2S80M53373N169M");
-            let results = cig.does_it_match_an_intervall(&500, 550, 580);
-            assert_eq!(results, false)
-            //assert_eq!(results, None)
-        } 
-        #[test]  
-        fn test_match_s_eq_e(){
-            let cig = Cigar::from("2S80M53373N169M");
-            let results = cig.does_it_match_an_intervall(&500, 550, 550);
-            assert_eq!(results, true)
-            //assert_eq!(results, None)
-        }   
-        #[test]  
-        fn soft(){
-            let cig = Cigar::from("100M45S");
-            let results = cig.soft_clipped_end(&Strand::Plus);
-            assert_eq!(results, true)
-            //assert_eq!(results, None)
-        }   
-        #[test]  
-        fn test_match_justinbound(){
-            let cig = Cigar::from("2S80M53373N169M45S");
-            let results = cig.does_it_match_an_intervall(&500, 575, 579);
-            assert_eq!(results, true)
-            //assert_eq!(results, None)
-        }   
-        #[test]  
-        fn softR(){
-            let cig = Cigar::from("2S80M53373N169M45S");
-            let results = cig.soft_clipped_end(&Strand::Minus);
-            assert_eq!(results, true)
-            //assert_eq!(results, None)
-        }   
     }
 }
 